@@ -0,0 +1,113 @@
+use crate::keep;
+use serde::Serialize;
+use std::path::Path;
+use tokio::fs;
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeleteAction {
+    // 删除了目录及其内容
+    RemovedDirectory,
+    // 删除了普通文件
+    RemovedFile,
+    // 路径是符号链接，只移除了链接本身，链接目标保持不变
+    UnlinkedSymlink,
+    // 路径已不存在，跳过
+    NotFound,
+    // 路径本身或其下的目录中有保护标记文件，跳过
+    Protected,
+    // 路径既不是目录也不是普通文件（例如设备文件、管道、套接字），跳过
+    Unsupported,
+}
+
+// delete_folders 对每个路径的处理结果
+#[derive(Clone, Debug, Serialize)]
+pub struct DeleteOutcome {
+    pub path: String,
+    pub action: DeleteAction,
+    // 因保护标记跳过时，含有标记文件的目录
+    pub protected: Option<String>,
+}
+
+// 先检查全部路径，包含保护标记的路径跳过并在结果中返回，其余路径照常删除；遇到第一个删除失败时停止
+pub async fn delete_paths(paths: Vec<String>, keep_markers: Vec<String>) -> Result<Vec<DeleteOutcome>, String> {
+    let checked = paths.clone();
+    let protected = tauri::async_runtime::spawn_blocking(move || {
+        checked
+            .iter()
+            .map(|path| keep::find_protected(Path::new(path), &keep_markers))
+            .collect::<Vec<_>>()
+    })
+    .await
+    .map_err(|e| format!("检查保护标记失败: {}", e))?;
+
+    let mut outcomes = Vec::with_capacity(paths.len());
+    for (path_str, protected) in paths.into_iter().zip(protected) {
+        if let Some(dir) = protected {
+            println!("跳过受保护的路径: {}（{} 中有保护标记）", &path_str, dir.display());
+            outcomes.push(DeleteOutcome {
+                path: path_str,
+                action: DeleteAction::Protected,
+                protected: Some(dir.display().to_string()),
+            });
+            continue;
+        }
+        println!("尝试删除: {}", &path_str);
+        let action = delete_path(&path_str).await?;
+        outcomes.push(DeleteOutcome { path: path_str, action, protected: None });
+    }
+    println!("删除操作完成");
+    Ok(outcomes)
+}
+
+async fn delete_path(path_str: &str) -> Result<DeleteAction, String> {
+    let path = Path::new(path_str);
+    // 不跟随链接，才能区分链接本身和它指向的目录
+    let metadata = match fs::symlink_metadata(path).await {
+        Ok(metadata) => metadata,
+        Err(_) => {
+            println!("路径不存在: {}", path_str);
+            return Ok(DeleteAction::NotFound);
+        }
+    };
+
+    let action = if metadata.file_type().is_symlink() {
+        // unix 上目录链接也是文件；Windows 的目录链接和联接点需要用 remove_dir 移除，两者都不会跟随链接
+        let result = match fs::remove_file(path).await {
+            Ok(()) => Ok(()),
+            Err(_) => fs::remove_dir(path).await,
+        };
+        result.map_err(|e| {
+            let error_msg = format!("删除链接失败: {}，错误: {}", path_str, e);
+            eprintln!("{}", error_msg);
+            error_msg
+        })?;
+        DeleteAction::UnlinkedSymlink
+    } else if metadata.is_dir() {
+        fs::remove_dir_all(path).await.map_err(|e| {
+            let error_msg = format!("删除失败: {}，错误: {}", path_str, e);
+            eprintln!("{}", error_msg);
+            error_msg
+        })?;
+        DeleteAction::RemovedDirectory
+    } else if metadata.is_file() {
+        fs::remove_file(path).await.map_err(|e| {
+            let error_msg = format!("删除文件失败: {}，错误: {}", path_str, e);
+            eprintln!("{}", error_msg);
+            error_msg
+        })?;
+        DeleteAction::RemovedFile
+    } else {
+        println!("路径不是目录或普通文件: {}", path_str);
+        return Ok(DeleteAction::Unsupported);
+    };
+
+    // 验证文件或目录是否已被删除
+    if fs::symlink_metadata(path).await.is_ok() {
+        let error_msg = format!("验证失败，未删除: {}", path_str);
+        eprintln!("{}", error_msg);
+        return Err(error_msg);
+    }
+    println!("成功删除: {}", path_str);
+    Ok(action)
+}
@@ -0,0 +1,160 @@
+use crate::search::{MatchRecord, SearchEvent, SearchId, SearchSession};
+use crate::size::FolderSize;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+use tokio::sync::{mpsc, Notify};
+
+// 遍历线程到转发任务之间的通道容量
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+// 每批最多的匹配数和大小数，以及不满一批时的最长等待时间
+const BATCH_SIZE: usize = 200;
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+// 开启确认时，最多允许多少个批次未被前端确认
+const MAX_UNACKED_BATCHES: u64 = 2;
+
+// folder-found-batch 事件的载荷，sizes 为已发出匹配的大小，通常与匹配在同一批
+#[derive(Clone, Debug, Serialize)]
+pub struct FoundBatch {
+    pub search_id: SearchId,
+    pub batch_id: u64,
+    pub matches: Vec<MatchRecord>,
+    pub sizes: Vec<FolderSize>,
+}
+
+// 前端已确认的批次，批次 id 从 1 开始递增
+pub struct BatchAcks {
+    acked: AtomicU64,
+    notify: Notify,
+}
+
+impl BatchAcks {
+    pub fn new() -> Self {
+        Self {
+            acked: AtomicU64::new(0),
+            notify: Notify::new(),
+        }
+    }
+
+    pub fn ack(&self, batch_id: u64) {
+        self.acked.fetch_max(batch_id, Ordering::Relaxed);
+        self.notify.notify_waiters();
+    }
+
+    // 等到前端确认了 batch_id 之前足够多的批次；搜索被停止后不再等待
+    async fn wait_for_room(&self, batch_id: u64, session: &SearchSession) {
+        while batch_id > self.acked.load(Ordering::Relaxed) + MAX_UNACKED_BATCHES
+            && session.continue_search()
+        {
+            let _ = tokio::time::timeout(FLUSH_INTERVAL, self.notify.notified()).await;
+        }
+    }
+}
+
+struct Batcher {
+    app: AppHandle,
+    search_id: SearchId,
+    session: Arc<SearchSession>,
+    acks: Option<Arc<BatchAcks>>,
+    matches: Vec<MatchRecord>,
+    sizes: Vec<FolderSize>,
+    next_batch_id: u64,
+}
+
+impl Batcher {
+    fn is_full(&self) -> bool {
+        self.matches.len() + self.sizes.len() >= BATCH_SIZE
+    }
+
+    async fn flush(&mut self) {
+        if self.matches.is_empty() && self.sizes.is_empty() {
+            return;
+        }
+        let batch_id = self.next_batch_id;
+        self.next_batch_id += 1;
+        if let Some(acks) = &self.acks {
+            acks.wait_for_room(batch_id, &self.session).await;
+        }
+
+        let batch = FoundBatch {
+            search_id: self.search_id,
+            batch_id,
+            matches: std::mem::take(&mut self.matches),
+            sizes: std::mem::take(&mut self.sizes),
+        };
+        self.app.emit_all("folder-found-batch", &batch)
+            .expect("Failed to emit folder found batch event");
+    }
+}
+
+// 把遍历线程发来的事件转发给前端，匹配结果按数量或时间窗口合并成批次
+// acks 为 None 时不等待前端确认
+pub async fn forward_search_events(
+    app: AppHandle,
+    search_id: SearchId,
+    session: Arc<SearchSession>,
+    acks: Option<Arc<BatchAcks>>,
+    mut receiver: mpsc::Receiver<SearchEvent>,
+) {
+    let mut batcher = Batcher {
+        app: app.clone(),
+        search_id,
+        session,
+        acks,
+        matches: Vec::with_capacity(BATCH_SIZE),
+        sizes: Vec::new(),
+        next_batch_id: 1,
+    };
+    let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+
+    loop {
+        let event = tokio::select! {
+            event = receiver.recv() => event,
+            _ = ticker.tick() => {
+                batcher.flush().await;
+                continue;
+            }
+        };
+
+        let result = match event {
+            Some(SearchEvent::Found(record)) => {
+                batcher.matches.push(record);
+                if batcher.is_full() {
+                    batcher.flush().await;
+                }
+                continue;
+            }
+            Some(SearchEvent::Size(size)) => {
+                batcher.sizes.push(size);
+                if batcher.is_full() {
+                    batcher.flush().await;
+                }
+                continue;
+            }
+            Some(SearchEvent::Skipped(record)) => app.emit_all("skip-folder-found", &record),
+            Some(SearchEvent::Protected(record)) => app.emit_all("protected-folder-found", &record),
+            Some(SearchEvent::MountPointSkipped(skipped)) => app.emit_all("mount-point-skipped", &skipped),
+            Some(SearchEvent::Progress(progress)) => app.emit_all("search-progress", &progress),
+            Some(SearchEvent::Error(error)) => app.emit_all("search-error", &error),
+            Some(SearchEvent::Warning(warning)) => app.emit_all("search-warning", &warning),
+            // 结束类事件之前先把缓冲的结果发出去
+            Some(SearchEvent::NoneFound(none_found)) => {
+                batcher.flush().await;
+                app.emit_all("no-folders-found", &none_found)
+            }
+            // Finished 是遍历线程发出的最后一个事件，之后不再等待前端确认，避免前端不再确认时卡住
+            Some(SearchEvent::Finished(finished)) => {
+                batcher.acks = None;
+                batcher.flush().await;
+                app.emit_all("search-finished", &finished)
+            }
+            None => {
+                batcher.flush().await;
+                break;
+            }
+        };
+        result.expect("Failed to emit search event");
+    }
+}
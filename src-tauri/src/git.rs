@@ -0,0 +1,980 @@
+use flate2::read::ZlibDecoder;
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::UNIX_EPOCH;
+
+// 直接读取 .git 目录里的 refs、对象和索引，不依赖 git 命令
+// 只支持 SHA-1 仓库，SHA-256 仓库视为无法读取
+
+type ObjectId = [u8; 20];
+
+// 解析 ref 时允许的最大符号引用层数，以及 pack 中增量链的最大长度
+const MAX_SYMREF_DEPTH: usize = 10;
+const MAX_DELTA_CHAIN: usize = 10_000;
+// 索引和树中子模块条目的 mode
+const GITLINK_MODE: u32 = 0o160000;
+const SYMLINK_MODE: u32 = 0o120000;
+
+// 匹配所在仓库的状态，随匹配结果一起发给前端
+#[derive(Clone, Debug, Serialize)]
+pub struct GitStatus {
+    // 仓库工作树根目录
+    pub repository: String,
+    // HEAD 指向的提交，新建的空仓库为 None
+    pub head: Option<String>,
+    // HEAD 提交的提交时间，Unix 秒
+    pub commit_time: Option<u64>,
+    // 有未提交的改动（已暂存或工作树中已跟踪文件被修改、删除）
+    pub dirty: bool,
+}
+
+pub struct Repository {
+    work_tree: PathBuf,
+    git_dir: PathBuf,
+    // git worktree 的 refs 和对象放在主仓库里
+    common_dir: PathBuf,
+}
+
+// 从 start 开始逐级向上查找 .git，.git 既可以是目录，也可以是子模块和 worktree 使用的 gitdir 文件
+pub fn discover(start: &Path) -> Option<Repository> {
+    for dir in start.ancestors() {
+        let dot_git = dir.join(".git");
+        let metadata = match fs::metadata(&dot_git) {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        let git_dir = if metadata.is_dir() {
+            dot_git
+        } else {
+            let content = match fs::read_to_string(&dot_git) {
+                Ok(c) => c,
+                Err(_) => continue,
+            };
+            match content.lines().find_map(|line| line.strip_prefix("gitdir:")) {
+                // 相对路径相对 .git 文件所在目录
+                Some(target) => dir.join(target.trim()),
+                None => continue,
+            }
+        };
+        if !git_dir.join("HEAD").is_file() {
+            continue;
+        }
+
+        let common_dir = match fs::read_to_string(git_dir.join("commondir")) {
+            Ok(common) => git_dir.join(common.trim()),
+            Err(_) => git_dir.clone(),
+        };
+        return Some(Repository {
+            work_tree: dir.to_path_buf(),
+            git_dir,
+            common_dir,
+        });
+    }
+    None
+}
+
+impl Repository {
+    pub fn work_tree(&self) -> &Path {
+        &self.work_tree
+    }
+
+    pub fn git_dir(&self) -> &Path {
+        &self.git_dir
+    }
+
+    // 索引中跟踪的所有路径，索引无法解析时返回 None
+    pub fn tracked_paths(&self) -> Option<TrackedPaths> {
+        let index = read_index(&self.git_dir.join("index"))?;
+        let mut paths: Vec<String> = index.entries.into_iter().map(|entry| entry.path).collect();
+        paths.sort();
+        Some(TrackedPaths(paths))
+    }
+
+    // 读取 HEAD 提交和改动状态，should_continue 返回 false 时尽快返回（dirty 可能不准确）
+    pub fn status(&self, should_continue: impl Fn() -> bool) -> GitStatus {
+        let store = ObjectStore::open(&self.common_dir.join("objects"));
+        let head = self.resolve_ref("HEAD");
+        let commit = head.and_then(|id| match store.read(&id) {
+            Some((ObjectKind::Commit, data)) => Some(data),
+            _ => None,
+        });
+        let commit_time = commit.as_deref().and_then(commit_time);
+        let head_tree = commit.as_deref().and_then(commit_tree);
+
+        let dirty = match read_index(&self.git_dir.join("index")) {
+            Some(index) => {
+                self.worktree_changed(&index, &should_continue)
+                    || (should_continue() && index_changed(&store, &index, head_tree))
+            }
+            // 索引无法解析时不能确定，按没有改动处理
+            None => false,
+        };
+
+        GitStatus {
+            repository: self.work_tree.display().to_string(),
+            head: head.map(|id| to_hex(&id)),
+            commit_time,
+            dirty,
+        }
+    }
+
+    fn resolve_ref(&self, name: &str) -> Option<ObjectId> {
+        let mut name = name.to_string();
+        for _ in 0..MAX_SYMREF_DEPTH {
+            // HEAD 等是每个 worktree 自己的，refs/heads 等在公共目录
+            let loose = fs::read_to_string(self.git_dir.join(&name))
+                .or_else(|_| fs::read_to_string(self.common_dir.join(&name)));
+            let content = match loose {
+                Ok(content) => content,
+                Err(_) => return self.packed_ref(&name),
+            };
+            let content = content.trim();
+            match content.strip_prefix("ref:") {
+                Some(target) => name = target.trim().to_string(),
+                None => return parse_hex(content),
+            }
+        }
+        None
+    }
+
+    // packed-refs 每行为 "<id> <ref>"，# 开头为注释，^ 开头为上一个标签指向的对象
+    fn packed_ref(&self, name: &str) -> Option<ObjectId> {
+        let content = fs::read_to_string(self.common_dir.join("packed-refs")).ok()?;
+        content
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+            .find_map(|line| {
+                let (id, ref_name) = line.split_once(' ')?;
+                if ref_name.trim() == name {
+                    parse_hex(id)
+                } else {
+                    None
+                }
+            })
+    }
+
+    // 已跟踪文件在工作树中被修改或删除；stat 信息与索引一致时认为未改动，否则比较内容哈希
+    // 未跟踪的新文件不计入
+    fn worktree_changed(&self, index: &Index, should_continue: &impl Fn() -> bool) -> bool {
+        for (check_counter, entry) in index.entries.iter().enumerate() {
+            if check_counter % 1000 == 0 && !should_continue() {
+                return false;
+            }
+            // 未解决的合并冲突
+            if entry.stage != 0 {
+                return true;
+            }
+            if entry.mode == GITLINK_MODE || entry.skip_worktree || entry.assume_valid {
+                continue;
+            }
+
+            let path = self.work_tree.join(&entry.path);
+            let metadata = match fs::symlink_metadata(&path) {
+                Ok(m) => m,
+                Err(_) => return true,
+            };
+            // 索引只保存大小的低 32 位
+            if metadata.len() as u32 != entry.size {
+                return true;
+            }
+            let mtime = metadata.modified().ok().and_then(|t| t.duration_since(UNIX_EPOCH).ok());
+            let same_mtime = mtime.is_some_and(|d| {
+                d.as_secs() as u32 == entry.mtime.0 && (entry.mtime.1 == 0 || d.subsec_nanos() == entry.mtime.1)
+            });
+            if same_mtime {
+                continue;
+            }
+
+            let content = if entry.mode == SYMLINK_MODE {
+                fs::read_link(&path).ok().map(|target| target.to_string_lossy().into_owned().into_bytes())
+            } else {
+                fs::read(&path).ok()
+            };
+            match content {
+                Some(content) if hash_object("blob", &content) == entry.id => continue,
+                _ => return true,
+            }
+        }
+        false
+    }
+}
+
+// 索引与 HEAD 提交的树不同，即有已暂存未提交的改动
+fn index_changed(store: &ObjectStore, index: &Index, head_tree: Option<ObjectId>) -> bool {
+    let head_tree = match head_tree {
+        Some(tree) => tree,
+        // 还没有提交时，索引里有内容就算有改动
+        None => return !index.entries.is_empty(),
+    };
+    // 索引的 TREE 扩展缓存了根目录的树对象，仍然有效时直接比较
+    if let Some(cached) = index.cached_root_tree {
+        return cached != head_tree;
+    }
+
+    let mut tree_entries = HashMap::new();
+    if !store.collect_tree(&head_tree, "", &mut tree_entries) {
+        return false;
+    }
+    tree_entries.len() != index.entries.len()
+        || index
+            .entries
+            .iter()
+            .any(|entry| tree_entries.get(&entry.path) != Some(&entry.id))
+}
+
+// 索引中跟踪的路径，按字节序排列，路径以 / 分隔并相对工作树根目录
+pub struct TrackedPaths(Vec<String>);
+
+impl TrackedPaths {
+    // path 本身或其下任一文件被跟踪
+    pub fn contains(&self, path: &str) -> bool {
+        let start = self.0.partition_point(|tracked| tracked.as_str() < path);
+        self.0[start..]
+            .iter()
+            .take_while(|tracked| tracked.starts_with(path))
+            .any(|tracked| tracked.len() == path.len() || tracked.as_bytes()[path.len()] == b'/')
+    }
+}
+
+// 同一次搜索中按仓库缓存状态，同一仓库下的多个匹配只读取一次
+#[derive(Default)]
+pub struct StatusCache {
+    repositories: Mutex<HashMap<PathBuf, Arc<OnceLock<GitStatus>>>>,
+}
+
+impl StatusCache {
+    // start 所在仓库的状态，不在任何仓库中时返回 None
+    pub fn status(&self, start: &Path, should_continue: impl Fn() -> bool) -> Option<GitStatus> {
+        let repository = discover(start)?;
+        let cell = self
+            .repositories
+            .lock()
+            .unwrap()
+            .entry(repository.work_tree().to_path_buf())
+            .or_default()
+            .clone();
+        // 在锁外读取，不同仓库可以并行
+        Some(cell.get_or_init(|| repository.status(should_continue)).clone())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ObjectKind {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+}
+
+impl ObjectKind {
+    fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"commit" => Some(ObjectKind::Commit),
+            b"tree" => Some(ObjectKind::Tree),
+            b"blob" => Some(ObjectKind::Blob),
+            b"tag" => Some(ObjectKind::Tag),
+            _ => None,
+        }
+    }
+
+    fn from_pack_type(kind: u8) -> Option<Self> {
+        match kind {
+            1 => Some(ObjectKind::Commit),
+            2 => Some(ObjectKind::Tree),
+            3 => Some(ObjectKind::Blob),
+            4 => Some(ObjectKind::Tag),
+            _ => None,
+        }
+    }
+}
+
+// pack 中的一个条目，增量条目需要先读出基础对象
+enum PackEntry {
+    Object(ObjectKind, Vec<u8>),
+    OfsDelta(u64, Vec<u8>),
+    RefDelta(ObjectId, Vec<u8>),
+}
+
+// 版本 2 的 .idx 文件：魔数、版本、256 项 fanout 表、对象 id、CRC、32 位偏移、64 位偏移
+struct PackIndex {
+    data: Vec<u8>,
+    count: usize,
+    pack: PathBuf,
+}
+
+const PACK_INDEX_MAGIC: &[u8] = b"\xfftOc";
+const PACK_INDEX_HEADER: usize = 8 + 256 * 4;
+
+impl PackIndex {
+    fn open(path: &Path) -> Option<Self> {
+        let data = fs::read(path).ok()?;
+        if data.len() < PACK_INDEX_HEADER || &data[0..4] != PACK_INDEX_MAGIC || read_u32(&data, 4)? != 2 {
+            return None;
+        }
+        let count = read_u32(&data, 8 + 255 * 4)? as usize;
+        if data.len() < PACK_INDEX_HEADER + count * 28 {
+            return None;
+        }
+        // 扇出表必须单调不减，最后一项等于对象数，否则视为损坏
+        let mut previous = 0;
+        for byte in 0..256 {
+            let fanout = read_u32(&data, 8 + byte * 4)?;
+            if fanout < previous {
+                return None;
+            }
+            previous = fanout;
+        }
+        Some(Self {
+            data,
+            count,
+            pack: path.with_extension("pack"),
+        })
+    }
+
+    fn fanout(&self, byte: usize) -> usize {
+        read_u32(&self.data, 8 + byte * 4).unwrap_or(0) as usize
+    }
+
+    // 对象在 pack 文件中的偏移
+    fn find(&self, id: &ObjectId) -> Option<u64> {
+        let first = id[0] as usize;
+        let mut low = if first == 0 { 0 } else { self.fanout(first - 1) };
+        let mut high = self.fanout(first);
+        while low < high {
+            let middle = (low + high) / 2;
+            let start = PACK_INDEX_HEADER + middle * 20;
+            match self.data.get(start..start + 20)?.cmp(id) {
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+                std::cmp::Ordering::Equal => return self.offset(middle),
+            }
+        }
+        None
+    }
+
+    fn offset(&self, position: usize) -> Option<u64> {
+        let offsets = PACK_INDEX_HEADER + self.count * 24;
+        let offset = read_u32(&self.data, offsets + position * 4)?;
+        if offset & 0x8000_0000 == 0 {
+            return Some(offset as u64);
+        }
+        // 最高位为 1 时，其余位是 64 位偏移表的下标
+        let large = offsets + self.count * 4 + (offset & 0x7fff_ffff) as usize * 8;
+        let bytes = self.data.get(large..large + 8)?;
+        Some(u64::from_be_bytes(bytes.try_into().ok()?))
+    }
+}
+
+struct ObjectStore {
+    objects_dir: PathBuf,
+    packs: Vec<PackIndex>,
+}
+
+impl ObjectStore {
+    fn open(objects_dir: &Path) -> Self {
+        let packs = fs::read_dir(objects_dir.join("pack"))
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|ext| ext == "idx"))
+                    .filter_map(|path| PackIndex::open(&path))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            objects_dir: objects_dir.to_path_buf(),
+            packs,
+        }
+    }
+
+    fn read(&self, id: &ObjectId) -> Option<(ObjectKind, Vec<u8>)> {
+        if let Some(object) = self.read_loose(id) {
+            return Some(object);
+        }
+        self.packs
+            .iter()
+            .find_map(|pack| pack.find(id).map(|offset| (pack, offset)))
+            .and_then(|(pack, offset)| self.read_packed(pack, offset))
+    }
+
+    // 松散对象：objects/xx/yyyy，zlib 压缩的 "<类型> <大小>\0<内容>"
+    fn read_loose(&self, id: &ObjectId) -> Option<(ObjectKind, Vec<u8>)> {
+        let hex = to_hex(id);
+        let file = File::open(self.objects_dir.join(&hex[..2]).join(&hex[2..])).ok()?;
+        let mut data = Vec::new();
+        ZlibDecoder::new(file).read_to_end(&mut data).ok()?;
+
+        let header_end = data.iter().position(|&b| b == 0)?;
+        let kind = data[..header_end].split(|&b| b == b' ').next()?;
+        let kind = ObjectKind::from_name(kind)?;
+        data.drain(..=header_end);
+        Some((kind, data))
+    }
+
+    fn read_packed(&self, pack: &PackIndex, mut offset: u64) -> Option<(ObjectKind, Vec<u8>)> {
+        let mut file = BufReader::new(File::open(&pack.pack).ok()?);
+        // 沿增量链找到基础对象，再按相反顺序应用增量
+        let mut deltas = Vec::new();
+        let (kind, mut data) = loop {
+            if deltas.len() > MAX_DELTA_CHAIN {
+                return None;
+            }
+            match read_pack_entry(&mut file, offset)? {
+                PackEntry::Object(kind, data) => break (kind, data),
+                PackEntry::OfsDelta(base_offset, delta) => {
+                    deltas.push(delta);
+                    offset = base_offset;
+                }
+                PackEntry::RefDelta(base, delta) => {
+                    deltas.push(delta);
+                    break self.read(&base)?;
+                }
+            }
+        };
+        for delta in deltas.iter().rev() {
+            data = apply_delta(&data, delta)?;
+        }
+        Some((kind, data))
+    }
+
+    // 递归收集树中所有文件的 路径 -> 对象 id，返回 false 表示有对象无法读取
+    fn collect_tree(&self, id: &ObjectId, prefix: &str, entries: &mut HashMap<String, ObjectId>) -> bool {
+        let data = match self.read(id) {
+            Some((ObjectKind::Tree, data)) => data,
+            _ => return false,
+        };
+        // 每个条目为 "<八进制 mode> <名称>\0<20 字节 id>"
+        let mut rest = &data[..];
+        while !rest.is_empty() {
+            let space = match rest.iter().position(|&b| b == b' ') {
+                Some(i) => i,
+                None => return false,
+            };
+            let nul = match rest.iter().position(|&b| b == 0) {
+                Some(i) if i + 21 <= rest.len() && i > space => i,
+                _ => return false,
+            };
+            let mode = &rest[..space];
+            let name = String::from_utf8_lossy(&rest[space + 1..nul]);
+            let mut child = [0u8; 20];
+            child.copy_from_slice(&rest[nul + 1..nul + 21]);
+            rest = &rest[nul + 21..];
+
+            let path = if prefix.is_empty() {
+                name.into_owned()
+            } else {
+                format!("{}/{}", prefix, name)
+            };
+            if mode == b"40000" {
+                if !self.collect_tree(&child, &path, entries) {
+                    return false;
+                }
+            } else {
+                entries.insert(path, child);
+            }
+        }
+        true
+    }
+}
+
+fn read_pack_entry(file: &mut BufReader<File>, offset: u64) -> Option<PackEntry> {
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    // 类型和大小：第一个字节的 4-6 位为类型，其余为小端 7 位分组的大小
+    let mut byte = read_byte(file)?;
+    let kind = (byte >> 4) & 0x7;
+    while byte & 0x80 != 0 {
+        byte = read_byte(file)?;
+    }
+
+    let entry = match kind {
+        6 => {
+            let distance = read_offset_varint(|| read_byte(file))?;
+            let base_offset = offset.checked_sub(distance)?;
+            PackEntry::OfsDelta(base_offset, inflate(file)?)
+        }
+        7 => {
+            let mut base = [0u8; 20];
+            file.read_exact(&mut base).ok()?;
+            PackEntry::RefDelta(base, inflate(file)?)
+        }
+        kind => PackEntry::Object(ObjectKind::from_pack_type(kind)?, inflate(file)?),
+    };
+    Some(entry)
+}
+
+fn inflate(reader: &mut impl Read) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    ZlibDecoder::new(reader).read_to_end(&mut data).ok()?;
+    Some(data)
+}
+
+fn read_byte(reader: &mut impl Read) -> Option<u8> {
+    let mut byte = [0u8; 1];
+    reader.read_exact(&mut byte).ok()?;
+    Some(byte[0])
+}
+
+// OFS_DELTA 偏移和索引 v4 路径前缀使用的变长整数，每多一个字节先加 1
+fn read_offset_varint(mut next: impl FnMut() -> Option<u8>) -> Option<u64> {
+    let mut byte = next()?;
+    let mut value = (byte & 0x7f) as u64;
+    while byte & 0x80 != 0 {
+        byte = next()?;
+        value = ((value + 1) << 7) | (byte & 0x7f) as u64;
+    }
+    Some(value)
+}
+
+// 增量数据：源大小、目标大小，之后是复制（最高位为 1）和插入指令
+fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let mut position = 0;
+    let mut size = || {
+        let mut value = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = *delta.get(position)?;
+            position += 1;
+            // 移位超过 usize 的位数只可能来自损坏的数据
+            value |= ((byte & 0x7f) as usize).checked_shl(shift)?;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+    };
+    let source_size = size()?;
+    let target_size = size()?;
+    if source_size != base.len() {
+        return None;
+    }
+
+    // 每条指令最多复制 0xffffff 字节，目标大小不可能超过这个上限，否则按损坏处理而不是预先分配
+    let max_target_size = (delta.len() - position).saturating_mul(0xff_ffff);
+    if target_size > max_target_size {
+        return None;
+    }
+    let mut result = Vec::with_capacity(target_size);
+    while position < delta.len() {
+        let op = delta[position];
+        position += 1;
+        if op & 0x80 != 0 {
+            let mut copy_offset = 0usize;
+            let mut copy_size = 0usize;
+            for bit in 0..7 {
+                if op & (1 << bit) == 0 {
+                    continue;
+                }
+                let byte = *delta.get(position)? as usize;
+                position += 1;
+                if bit < 4 {
+                    copy_offset |= byte << (bit * 8);
+                } else {
+                    copy_size |= byte << ((bit - 4) * 8);
+                }
+            }
+            if copy_size == 0 {
+                copy_size = 0x10000;
+            }
+            result.extend_from_slice(base.get(copy_offset..copy_offset.checked_add(copy_size)?)?);
+        } else if op != 0 {
+            let end = position + op as usize;
+            result.extend_from_slice(delta.get(position..end)?);
+            position = end;
+        } else {
+            return None;
+        }
+    }
+
+    if result.len() == target_size {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+// 提交头部中 committer 行的时间戳："committer 名字 <邮箱> 1700000000 +0800"
+fn commit_time(commit: &[u8]) -> Option<u64> {
+    commit_headers(commit).find_map(|line| {
+        let rest = line.strip_prefix("committer ")?;
+        let mut parts = rest.rsplitn(3, ' ');
+        parts.next()?;
+        parts.next()?.parse().ok()
+    })
+}
+
+fn commit_tree(commit: &[u8]) -> Option<ObjectId> {
+    commit_headers(commit).find_map(|line| parse_hex(line.strip_prefix("tree ")?))
+}
+
+// 提交头部到第一个空行为止
+fn commit_headers(commit: &[u8]) -> impl Iterator<Item = &str> {
+    let end = commit.windows(2).position(|w| w == b"\n\n").unwrap_or(commit.len());
+    std::str::from_utf8(&commit[..end]).unwrap_or("").lines()
+}
+
+struct IndexEntry {
+    path: String,
+    id: ObjectId,
+    mode: u32,
+    size: u32,
+    // (秒, 纳秒)
+    mtime: (u32, u32),
+    stage: u16,
+    assume_valid: bool,
+    skip_worktree: bool,
+}
+
+struct Index {
+    entries: Vec<IndexEntry>,
+    cached_root_tree: Option<ObjectId>,
+}
+
+// 解析 .git/index（版本 2-4），文件不存在时视为空索引
+fn read_index(path: &Path) -> Option<Index> {
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Some(Index { entries: Vec::new(), cached_root_tree: None })
+        }
+        Err(_) => return None,
+    };
+    if data.len() < 12 || &data[0..4] != b"DIRC" {
+        return None;
+    }
+    let version = read_u32(&data, 4)?;
+    if !(2..=4).contains(&version) {
+        return None;
+    }
+    let count = read_u32(&data, 8)? as usize;
+    // 每个条目至少 62 字节，条目数不可能超过文件能容纳的数量
+    if count > (data.len() - 12) / 62 {
+        return None;
+    }
+
+    let mut entries = Vec::with_capacity(count);
+    let mut position = 12;
+    let mut previous_path: Vec<u8> = Vec::new();
+    for _ in 0..count {
+        let start = position;
+        let fixed = data.get(start..start + 62)?;
+        let mut id = [0u8; 20];
+        id.copy_from_slice(&fixed[40..60]);
+        let flags = u16::from_be_bytes([fixed[60], fixed[61]]);
+        position += 62;
+
+        // 扩展标志（版本 3 起）
+        let mut skip_worktree = false;
+        if version >= 3 && flags & 0x4000 != 0 {
+            let extended = u16::from_be_bytes(data.get(position..position + 2)?.try_into().ok()?);
+            skip_worktree = extended & 0x4000 != 0;
+            position += 2;
+        }
+
+        let path = if version >= 4 {
+            // 版本 4 的路径相对上一条做前缀压缩：先去掉上一条末尾的 N 个字节，再接上本条后缀
+            let strip = read_offset_varint(|| {
+                let byte = data.get(position).copied();
+                position += 1;
+                byte
+            })? as usize;
+            let nul = position + data.get(position..)?.iter().position(|&b| b == 0)?;
+            let keep = previous_path.len().checked_sub(strip)?;
+            let mut path = previous_path[..keep].to_vec();
+            path.extend_from_slice(&data[position..nul]);
+            position = nul + 1;
+            path
+        } else {
+            // 版本 2、3 的条目用 1-8 个 NUL 补齐到 8 字节倍数
+            let nul = position + data.get(position..)?.iter().position(|&b| b == 0)?;
+            let path = data[position..nul].to_vec();
+            position = start + ((nul - start + 8) & !7);
+            path
+        };
+
+        entries.push(IndexEntry {
+            path: String::from_utf8_lossy(&path).into_owned(),
+            id,
+            mode: read_u32(fixed, 24)?,
+            size: read_u32(fixed, 36)?,
+            mtime: (read_u32(fixed, 8)?, read_u32(fixed, 12)?),
+            stage: (flags >> 12) & 0x3,
+            assume_valid: flags & 0x8000 != 0,
+            skip_worktree,
+        });
+        previous_path = path;
+    }
+
+    let cached_root_tree = read_cached_root_tree(&data, position);
+    Some(Index { entries, cached_root_tree })
+}
+
+// 条目之后是扩展：4 字节签名 + 4 字节长度 + 内容，末尾 20 字节为校验和
+// TREE 扩展的第一项是根目录："\0<条目数> <子树数>\n<树 id>"，条目数为 -1 表示已失效
+fn read_cached_root_tree(data: &[u8], mut position: usize) -> Option<ObjectId> {
+    while position + 8 <= data.len().saturating_sub(20) {
+        let signature = &data[position..position + 4];
+        let size = read_u32(data, position + 4)? as usize;
+        let content = data.get(position + 8..position + 8 + size)?;
+        position += 8 + size;
+        if signature != b"TREE" {
+            continue;
+        }
+
+        let newline = content.iter().position(|&b| b == b'\n')?;
+        let header = std::str::from_utf8(content.get(1..newline)?).ok()?;
+        if content[0] != 0 || header.starts_with('-') {
+            return None;
+        }
+        return content.get(newline + 1..newline + 21)?.try_into().ok();
+    }
+    None
+}
+
+fn hash_object(kind: &str, content: &[u8]) -> ObjectId {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("{} {}\0", kind, content.len()).as_bytes());
+    hasher.update(content);
+    hasher.finalize().into()
+}
+
+fn read_u32(data: &[u8], position: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(position..position + 4)?.try_into().ok()?))
+}
+
+fn parse_hex(hex: &str) -> Option<ObjectId> {
+    let hex = hex.trim();
+    if hex.len() != 40 {
+        return None;
+    }
+    let mut id = [0u8; 20];
+    for (i, byte) in id.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(id)
+}
+
+fn to_hex(id: &ObjectId) -> String {
+    id.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_temp(name: &str, data: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("foldermanage-git-{}-{}", std::process::id(), name));
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    // 62 字节的固定部分：mtime 1700000000.5、mode 100644、大小 5
+    fn fixed_entry(id_byte: u8, name_len: usize) -> Vec<u8> {
+        let mut entry = vec![0u8; 62];
+        entry[8..12].copy_from_slice(&1_700_000_000u32.to_be_bytes());
+        entry[12..16].copy_from_slice(&500_000_000u32.to_be_bytes());
+        entry[24..28].copy_from_slice(&0o100644u32.to_be_bytes());
+        entry[36..40].copy_from_slice(&5u32.to_be_bytes());
+        entry[40..60].copy_from_slice(&[id_byte; 20]);
+        entry[60..62].copy_from_slice(&(name_len as u16).to_be_bytes());
+        entry
+    }
+
+    fn v2_entry(id_byte: u8, path: &str) -> Vec<u8> {
+        let mut entry = fixed_entry(id_byte, path.len());
+        entry.extend_from_slice(path.as_bytes());
+        entry.resize(entry.len() + 8 - entry.len() % 8, 0);
+        entry
+    }
+
+    fn v4_entry(id_byte: u8, strip: u8, suffix: &str, name_len: usize) -> Vec<u8> {
+        let mut entry = fixed_entry(id_byte, name_len);
+        entry.push(strip);
+        entry.extend_from_slice(suffix.as_bytes());
+        entry.push(0);
+        entry
+    }
+
+    fn index_file(version: u32, entries: &[Vec<u8>], extensions: &[u8]) -> Vec<u8> {
+        let mut data = b"DIRC".to_vec();
+        data.extend_from_slice(&version.to_be_bytes());
+        data.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        for entry in entries {
+            data.extend_from_slice(entry);
+        }
+        data.extend_from_slice(extensions);
+        // 校验和不检查
+        data.extend_from_slice(&[0u8; 20]);
+        data
+    }
+
+    fn tree_extension(header: &str, id: Option<ObjectId>) -> Vec<u8> {
+        let mut content = vec![0u8];
+        content.extend_from_slice(header.as_bytes());
+        content.push(b'\n');
+        if let Some(id) = id {
+            content.extend_from_slice(&id);
+        }
+        let mut extension = b"TREE".to_vec();
+        extension.extend_from_slice(&(content.len() as u32).to_be_bytes());
+        extension.extend_from_slice(&content);
+        extension
+    }
+
+    fn paths(index: &Index) -> Vec<&str> {
+        index.entries.iter().map(|entry| entry.path.as_str()).collect()
+    }
+
+    #[test]
+    fn offset_varint_adds_one_per_continuation_byte() {
+        let decode = |bytes: &[u8]| {
+            let mut bytes = bytes.iter().copied();
+            read_offset_varint(|| bytes.next())
+        };
+        assert_eq!(decode(&[0x05]), Some(5));
+        assert_eq!(decode(&[0x7f]), Some(127));
+        assert_eq!(decode(&[0x80, 0x00]), Some(128));
+        assert_eq!(decode(&[0xff, 0x7f]), Some(16511));
+        assert_eq!(decode(&[0x80, 0x80, 0x00]), Some(16512));
+        assert_eq!(decode(&[0x80]), None);
+    }
+
+    #[test]
+    fn delta_copies_and_inserts() {
+        let base = b"hello world";
+        // 复制 base[0..6]，插入 "there "，再复制 base[6..11]
+        let mut delta = vec![11, 17, 0x90, 6, 6];
+        delta.extend_from_slice(b"there ");
+        delta.extend_from_slice(&[0x91, 6, 5]);
+        assert_eq!(apply_delta(base, &delta).as_deref(), Some(&b"hello there world"[..]));
+    }
+
+    #[test]
+    fn delta_copy_size_zero_means_64k() {
+        let base = vec![7u8; 0x10000];
+        // 源大小和目标大小都是 0x10000，变长编码为 80 80 04
+        let delta = [0x80, 0x80, 0x04, 0x80, 0x80, 0x04, 0x80];
+        assert_eq!(apply_delta(&base, &delta), Some(base));
+    }
+
+    #[test]
+    fn delta_rejects_invalid_input() {
+        let base = b"hello";
+        // 源大小不符
+        assert_eq!(apply_delta(base, &[4, 5, 0x90, 5]), None);
+        // 复制超出 base
+        assert_eq!(apply_delta(base, &[5, 6, 0x90, 6]), None);
+        // 保留的 0 指令
+        assert_eq!(apply_delta(base, &[5, 5, 0x00]), None);
+        // 插入的数据不足
+        assert_eq!(apply_delta(base, &[5, 5, 0x05, b'a']), None);
+        // 结果长度与目标大小不符
+        assert_eq!(apply_delta(base, &[5, 4, 0x90, 5]), None);
+    }
+
+    #[test]
+    fn commit_time_reads_committer_header() {
+        let commit = b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\
+            author A U Thor <author@example.com> 1600000000 +0000\n\
+            committer C O Mitter <committer@example.com> 1700000000 +0800\n\
+            \n\
+            committer Fake <fake@example.com> 5 +0000\n";
+        assert_eq!(commit_time(commit), Some(1_700_000_000));
+        assert_eq!(commit_time(b"tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\n\ncommitter x <x> 5 +0000\n"), None);
+    }
+
+    #[test]
+    fn tracked_paths_match_whole_components() {
+        let mut tracked = vec!["a/b/c.txt".to_string(), "a-b".to_string(), "ab".to_string(), "target/x".to_string()];
+        tracked.sort();
+        let tracked = TrackedPaths(tracked);
+        assert!(tracked.contains("a"));
+        assert!(tracked.contains("a/b"));
+        assert!(tracked.contains("a/b/c.txt"));
+        assert!(tracked.contains("ab"));
+        assert!(tracked.contains("target"));
+        assert!(!tracked.contains("a/b/c"));
+        assert!(!tracked.contains("tar"));
+        assert!(!tracked.contains("target/x/y"));
+        assert!(!tracked.contains("z"));
+    }
+
+    #[test]
+    fn index_v2_skips_padding() {
+        // 62 + 2 正好是 8 的倍数，需要补满 8 个 NUL
+        let entries = [v2_entry(1, "ab"), v2_entry(2, "c"), v2_entry(3, "dir/file.txt")];
+        let root = [9u8; 20];
+        let path = write_temp("index-v2", &index_file(2, &entries, &tree_extension("3 1", Some(root))));
+        let index = read_index(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(paths(&index), ["ab", "c", "dir/file.txt"]);
+        let entry = &index.entries[2];
+        assert_eq!(entry.id, [3u8; 20]);
+        assert_eq!(entry.mode, 0o100644);
+        assert_eq!(entry.size, 5);
+        assert_eq!(entry.mtime, (1_700_000_000, 500_000_000));
+        assert_eq!(index.cached_root_tree, Some(root));
+    }
+
+    #[test]
+    fn index_v4_expands_prefix_compression() {
+        let entries = [
+            v4_entry(1, 0, "dir/a.txt", 9),
+            v4_entry(2, 5, "b.txt", 9),
+            v4_entry(3, 6, "2/c", 6),
+        ];
+        // 根目录缓存失效时不使用
+        let path = write_temp("index-v4", &index_file(4, &entries, &tree_extension("-1 0", None)));
+        let index = read_index(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(paths(&index), ["dir/a.txt", "dir/b.txt", "dir2/c"]);
+        assert_eq!(index.entries[1].id, [2u8; 20]);
+        assert_eq!(index.cached_root_tree, None);
+    }
+
+    #[test]
+    fn delta_rejects_oversized_sizes() {
+        let base = b"hello";
+        // 目标大小超过指令能产生的上限时不预先分配
+        assert_eq!(apply_delta(base, &[5, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f, 0x90, 5]), None);
+        // 大小的变长编码超过 64 位
+        let mut delta = vec![5];
+        delta.extend_from_slice(&[0x80; 10]);
+        delta.push(0x01);
+        assert_eq!(apply_delta(base, &delta), None);
+    }
+
+    #[test]
+    fn index_rejects_corrupt_files() {
+        // 要去掉的字节数超过上一条路径的长度
+        let entries = [v4_entry(1, 0, "a", 1), v4_entry(2, 5, "b", 1)];
+        let path = write_temp("index-bad-strip", &index_file(4, &entries, &[]));
+        assert!(read_index(&path).is_none());
+        fs::remove_file(&path).unwrap();
+
+        // 条目数多于实际条目
+        let mut data = index_file(2, &[v2_entry(1, "a")], &[]);
+        data[8..12].copy_from_slice(&3u32.to_be_bytes());
+        let path = write_temp("index-truncated", &data);
+        assert!(read_index(&path).is_none());
+        fs::remove_file(&path).unwrap();
+
+        // 条目数远超文件大小时不能按它预先分配
+        let mut data = index_file(2, &[v2_entry(1, "a")], &[]);
+        data[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        let path = write_temp("index-huge-count", &data);
+        assert!(read_index(&path).is_none());
+        fs::remove_file(&path).unwrap();
+
+        // 不存在的索引视为空
+        let index = read_index(&std::env::temp_dir().join("foldermanage-git-missing-index")).unwrap();
+        assert!(index.entries.is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+use crate::git::{self, TrackedPaths};
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+// 匹配不能删除的原因
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtectReason {
+    // 目录本身或其中的文件被 git 跟踪
+    Tracked,
+    // 所在仓库的忽略规则没有忽略它
+    NotIgnored,
+    // 不在任何 git 仓库中，无法确认
+    NoRepository,
+}
+
+// 单个仓库的跟踪路径和 .git/info/exclude
+struct RepositoryRules {
+    tracked: Option<TrackedPaths>,
+    exclude: Option<Gitignore>,
+}
+
+// 按仓库的忽略规则判断匹配能否删除，同一次搜索中缓存已读取的忽略文件和索引
+#[derive(Default)]
+pub struct IgnoreChecker {
+    // 忽略文件路径 -> 编译好的规则，文件不存在时为 None
+    files: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+    repositories: Mutex<HashMap<PathBuf, Arc<RepositoryRules>>>,
+}
+
+impl IgnoreChecker {
+    // 返回 None 表示 path 被忽略且未被跟踪，可以安全删除
+    pub fn protection(&self, path: &Path, is_dir: bool) -> Option<ProtectReason> {
+        let parent = path.parent()?;
+        let repository = match git::discover(parent) {
+            Some(repository) => repository,
+            None => return Some(ProtectReason::NoRepository),
+        };
+        let work_tree = repository.work_tree();
+        let relative = match path.strip_prefix(work_tree) {
+            Ok(relative) => relative,
+            Err(_) => return Some(ProtectReason::NoRepository),
+        };
+        let rules = self.repository_rules(&repository);
+
+        let relative_name = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        // 索引无法读取时无法确认未被跟踪，按跟踪处理
+        if rules.tracked.as_ref().is_none_or(|tracked| tracked.contains(&relative_name)) {
+            return Some(ProtectReason::Tracked);
+        }
+
+        // 上级目录被忽略时其下所有内容都被忽略，所以从仓库根目录开始逐级检查
+        let mut current = work_tree.to_path_buf();
+        let count = relative.components().count();
+        for (i, component) in relative.components().enumerate() {
+            current.push(component);
+            let component_is_dir = i + 1 < count || is_dir;
+            if self.is_ignored(work_tree, &rules, &current, component_is_dir) {
+                return None;
+            }
+        }
+        Some(ProtectReason::NotIgnored)
+    }
+
+    // 从离 path 最近的 .gitignore 开始向上，第一个给出结论的文件生效，最后才是 .git/info/exclude
+    fn is_ignored(&self, work_tree: &Path, rules: &RepositoryRules, path: &Path, is_dir: bool) -> bool {
+        let parent = match path.parent() {
+            Some(parent) => parent,
+            None => return false,
+        };
+        for dir in parent.ancestors() {
+            if let Some(gitignore) = self.ignore_file(&dir.join(".gitignore")) {
+                match gitignore.matched(path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+            if dir == work_tree {
+                break;
+            }
+        }
+        rules
+            .exclude
+            .as_ref()
+            .is_some_and(|exclude| exclude.matched(path, is_dir).is_ignore())
+    }
+
+    fn ignore_file(&self, path: &Path) -> Option<Arc<Gitignore>> {
+        if let Some(cached) = self.files.lock().unwrap().get(path) {
+            return cached.clone();
+        }
+        let gitignore = if path.is_file() {
+            // 有语法错误的行会被跳过，其余规则照常生效
+            let (gitignore, _) = Gitignore::new(path);
+            Some(Arc::new(gitignore))
+        } else {
+            None
+        };
+        self.files.lock().unwrap().insert(path.to_path_buf(), gitignore.clone());
+        gitignore
+    }
+
+    fn repository_rules(&self, repository: &git::Repository) -> Arc<RepositoryRules> {
+        let work_tree = repository.work_tree();
+        if let Some(cached) = self.repositories.lock().unwrap().get(work_tree) {
+            return cached.clone();
+        }
+        // info/exclude 中的规则相对工作树根目录
+        let exclude_path = repository.git_dir().join("info").join("exclude");
+        let exclude = exclude_path.is_file().then(|| {
+            let mut builder = GitignoreBuilder::new(work_tree);
+            builder.add(&exclude_path);
+            builder.build().unwrap_or_else(|_| Gitignore::empty())
+        });
+        let rules = Arc::new(RepositoryRules {
+            tracked: repository.tracked_paths(),
+            exclude,
+        });
+        self.repositories.lock().unwrap().insert(work_tree.to_path_buf(), rules.clone());
+        rules
+    }
+}
+
+// 遍历时用于剪枝的忽略文件，同一目录中越靠前优先级越高
+const PRUNE_IGNORE_FILES: [&str; 3] = [".foldermanageignore", ".ignore", ".gitignore"];
+
+// 一个目录中的忽略文件，连同上级目录的规则组成一条链，子目录任务间共享
+pub struct IgnoreLayer {
+    parent: Option<Arc<IgnoreLayer>>,
+    matchers: Vec<Gitignore>,
+}
+
+impl IgnoreLayer {
+    // 搜索根目录的规则：根目录位于 git 仓库中时，还包括从仓库根目录到它之间的忽略文件
+    pub fn for_root(root: &Path) -> Arc<Self> {
+        let top = git::discover(root).map(|repository| repository.work_tree().to_path_buf());
+        let mut dirs = Vec::new();
+        for dir in root.ancestors() {
+            dirs.push(dir);
+            if top.as_deref().is_none_or(|top| top == dir) {
+                break;
+            }
+        }
+
+        let mut layer = Arc::new(Self { parent: None, matchers: Vec::new() });
+        for dir in dirs.into_iter().rev() {
+            layer = layer.child(dir);
+        }
+        layer
+    }
+
+    // 加上 dir 中的忽略文件，没有忽略文件时直接复用当前规则
+    pub fn child(self: &Arc<Self>, dir: &Path) -> Arc<Self> {
+        let matchers: Vec<Gitignore> = PRUNE_IGNORE_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .filter(|path| path.is_file())
+            .map(|path| Gitignore::new(path).0)
+            .collect();
+        if matchers.is_empty() {
+            return self.clone();
+        }
+        Arc::new(Self {
+            parent: Some(self.clone()),
+            matchers,
+        })
+    }
+
+    // 从最近的目录开始向上，第一个给出结论的规则生效
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut layer = Some(self);
+        while let Some(current) = layer {
+            for matcher in &current.matchers {
+                match matcher.matched(path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+            layer = current.parent.as_deref();
+        }
+        false
+    }
+}
@@ -0,0 +1,66 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use walkdir::WalkDir;
+
+// 目录中有这个文件时受保护：搜索时跳过，删除时拒绝删除它及其上级目录
+pub const DEFAULT_KEEP_MARKER: &str = ".foldermanage-keep";
+
+// 保护标记设置，由后端保存，搜索和删除使用同一份
+// 默认标记总是生效，设置的标记只是额外增加的一个
+pub struct KeepMarkers {
+    custom: RwLock<Option<String>>,
+}
+
+impl KeepMarkers {
+    pub fn new() -> Self {
+        Self { custom: RwLock::new(None) }
+    }
+
+    // 当前设置的标记，未设置时为默认标记
+    pub fn marker(&self) -> String {
+        self.custom
+            .read()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| DEFAULT_KEEP_MARKER.to_string())
+    }
+
+    // 标记必须是单个文件名，不能为空
+    pub fn set(&self, marker: &str) -> Result<(), String> {
+        let marker = marker.trim();
+        if marker.is_empty() || marker == "." || marker == ".." || marker.contains(['/', '\\']) {
+            return Err(format!("Invalid keep marker file name: '{}'", marker));
+        }
+        let custom = (marker != DEFAULT_KEEP_MARKER).then(|| marker.to_string());
+        *self.custom.write().unwrap() = custom;
+        Ok(())
+    }
+
+    // 需要检查的全部标记
+    pub fn markers(&self) -> Vec<String> {
+        let mut markers = vec![DEFAULT_KEEP_MARKER.to_string()];
+        markers.extend(self.custom.read().unwrap().clone());
+        markers
+    }
+}
+
+pub fn is_keep_marker(name: &OsStr, markers: &[String]) -> bool {
+    markers.iter().any(|marker| name == marker.as_str())
+}
+
+// path 本身或其下任一目录含有保护标记时，返回第一个受保护的目录
+pub fn find_protected(path: &Path, markers: &[String]) -> Option<PathBuf> {
+    // 不能删除保护标记文件本身
+    if path.file_name().is_some_and(|name| is_keep_marker(name, markers)) {
+        return path.parent().map(Path::to_path_buf);
+    }
+    // path 是符号链接时删除不会影响链接目标，不需要检查
+    WalkDir::new(path)
+        .follow_root_links(false)
+        .min_depth(1)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .find(|entry| is_keep_marker(entry.file_name(), markers))
+        .and_then(|entry| entry.path().parent().map(Path::to_path_buf))
+}
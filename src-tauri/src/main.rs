@@ -3,137 +3,203 @@
     windows_subsystem = "windows"
 )]
 
+mod delete;
+mod delivery;
+mod git;
+mod ignore_rules;
+mod keep;
+mod matcher;
+mod presets;
+mod rules;
+mod search;
+mod size;
+mod staleness;
+
+use std::collections::HashMap;
 use std::path::Path;
 use std::process::Command;
 use std::sync::Arc;
-use tauri::{command, Builder, Manager, State};
-use tokio::{fs, sync::Mutex};
-use walkdir::WalkDir;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use tauri::{command, Builder, RunEvent, State};
+use tokio::sync::{mpsc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use delivery::BatchAcks;
+use keep::KeepMarkers;
+use search::{SearchId, SearchOptions, SearchPlan, SearchSession};
+
+struct ActiveSearch {
+    session: Arc<SearchSession>,
+    acks: Arc<BatchAcks>,
+}
 
+// 所有进行中的搜索，按 id 独立控制
 struct SearchControl {
-    should_continue: AtomicBool,
+    sessions: std::sync::Mutex<HashMap<SearchId, ActiveSearch>>,
+    // 搜索工作线程，退出应用时等待它们结束
+    workers: std::sync::Mutex<Vec<thread::JoinHandle<()>>>,
+    next_search_id: AtomicU64,
 }
 
 impl SearchControl {
     fn new() -> Self {
         Self {
-            should_continue: AtomicBool::new(true),
+            sessions: std::sync::Mutex::new(HashMap::new()),
+            workers: std::sync::Mutex::new(Vec::new()),
+            next_search_id: AtomicU64::new(1),
         }
     }
 
-    fn stop(&self) {
-        self.should_continue.store(false, Ordering::Relaxed);
+    fn add_worker(&self, handle: thread::JoinHandle<()>) {
+        let mut workers = self.workers.lock().unwrap();
+        workers.retain(|h| !h.is_finished());
+        workers.push(handle);
     }
 
-    fn continue_search(&self) -> bool {
-        self.should_continue.load(Ordering::Relaxed)
+    // 停止所有搜索并等待工作线程退出
+    fn shutdown(&self) {
+        for active in self.sessions.lock().unwrap().values() {
+            active.session.stop();
+        }
+        let workers: Vec<_> = self.workers.lock().unwrap().drain(..).collect();
+        for handle in workers {
+            let _ = handle.join();
+        }
     }
 
-    fn reset(&self) {
-        self.should_continue.store(true, Ordering::Relaxed);
+    fn start(&self) -> (SearchId, Arc<SearchSession>, Arc<BatchAcks>) {
+        let id = self.next_search_id.fetch_add(1, Ordering::Relaxed);
+        let session = Arc::new(SearchSession::new());
+        let acks = Arc::new(BatchAcks::new());
+        let active = ActiveSearch {
+            session: session.clone(),
+            acks: acks.clone(),
+        };
+        self.sessions.lock().unwrap().insert(id, active);
+        (id, session, acks)
+    }
+
+    fn get(&self, id: SearchId) -> Option<Arc<SearchSession>> {
+        self.sessions.lock().unwrap().get(&id).map(|a| a.session.clone())
+    }
+
+    fn acks(&self, id: SearchId) -> Option<Arc<BatchAcks>> {
+        self.sessions.lock().unwrap().get(&id).map(|a| a.acks.clone())
+    }
+
+    fn finish(&self, id: SearchId) {
+        self.sessions.lock().unwrap().remove(&id);
     }
 }
 
+#[command]
+async fn stop_search(control: State<'_, Arc<SearchControl>>, id: SearchId) -> Result<(), String> {
+    let session = control
+        .get(id)
+        .ok_or_else(|| format!("Search session not found: {}", id))?;
+    session.stop();
+    Ok(())
+}
 
+#[command]
+async fn pause_search(control: State<'_, Arc<SearchControl>>, id: SearchId) -> Result<(), String> {
+    let session = control
+        .get(id)
+        .ok_or_else(|| format!("Search session not found: {}", id))?;
+    session.pause();
+    Ok(())
+}
 
 #[command]
-async fn stop_search(control: State<'_, Arc<SearchControl>>) -> Result<(), String> {
-    control.stop();
+async fn resume_search(control: State<'_, Arc<SearchControl>>, id: SearchId) -> Result<(), String> {
+    let session = control
+        .get(id)
+        .ok_or_else(|| format!("Search session not found: {}", id))?;
+    session.resume();
     Ok(())
 }
 
+// 前端处理完一批结果后确认，开启 ackBatches 时后端据此限速
+#[command]
+async fn ack_batch(control: State<'_, Arc<SearchControl>>, id: SearchId, batch_id: u64) -> Result<(), String> {
+    let acks = control
+        .acks(id)
+        .ok_or_else(|| format!("Search session not found: {}", id))?;
+    acks.ack(batch_id);
+    Ok(())
+}
+
+#[command]
+fn list_presets() -> Vec<presets::Preset> {
+    presets::presets()
+}
+
 struct AppState {
     is_deleting: bool,
 }
 
+// 启动搜索后立即返回搜索 id，结果通过事件推送，结束时发送 search-finished
 #[command]
 async fn search_folders(
     control: State<'_, Arc<SearchControl>>,
+    keep_markers: State<'_, Arc<KeepMarkers>>,
     app: tauri::AppHandle,
-    path: String,
-    foldername: String,
-    skipfolders: Vec<String>,
-    digin: bool,
-    fuzzy: bool,
-    casesense: bool,
-) -> Result<(), String> {
-    // 重置搜索控制状态以确保搜索可以开始
-    control.reset();
-    let mut entries = WalkDir::new(&path).min_depth(1).into_iter();
-
-    let mut count = 0;
-    let mut actual_digin = digin;
-    let foldername_to_match = if casesense { foldername.clone() } else { foldername.to_lowercase() };
-    let mut check_counter = 0;
-
-    // 如果任一 skipfolder 与 foldername 相等，设置 actual_digin 为 false
-    if skipfolders.iter().any(|s| case_insensitive_eq(s, &foldername, casesense)) {
-        actual_digin = false;
-    }
-
-    while let Some(entry) = entries.next() {
-         if check_counter % 1000 == 0 && !control.continue_search() {
-            break;
+    options: SearchOptions,
+) -> Result<SearchId, String> {
+    let plan = SearchPlan::new(options, keep_markers.markers())?;
+    let control = control.inner().clone();
+    let (search_id, session, acks) = control.start();
+    let pool = match search::build_pool(search_id, &plan.options) {
+        Ok(pool) => pool,
+        Err(e) => {
+            control.finish(search_id);
+            return Err(e);
         }
-        check_counter += 1;
-
-        let entry = match entry {
-            Ok(e) => e,
-            Err(_) => continue,
-        };
-
-        if entry.file_type().is_dir() {
-            let current_folder_name = entry.file_name().to_string_lossy().to_string();
-            let current_folder_name_to_match = if casesense { current_folder_name.clone() } else { current_folder_name.to_lowercase() };
-
-            if skipfolders.iter().any(|s| case_insensitive_eq(s, &current_folder_name, casesense)) {
-                app.emit_all("skip-folder-found", &entry.path().display().to_string())
-                    .expect("Failed to emit skip folder event");
-                entries.skip_current_dir();
-                continue;
-            }
-
-            let matches = if fuzzy {
-                current_folder_name_to_match.contains(&foldername_to_match)
-            } else {
-                current_folder_name_to_match == foldername_to_match
-            };
-
-            if matches {
-                count += 1; // 增加 count 计数
-                let path_str = entry.path().display().to_string();
-                app.emit_all("folder-found", &path_str).expect("Failed to emit event");
-
-                if !actual_digin {
-                    entries.skip_current_dir();
-                }
-            }
-        }
-    }
-    // 检查是否有文件夹被找到
-    if count == 0 {
-        app.emit_all("no-folders-found", &path)
-            .expect("Failed to emit no folders found event");
-    }
+    };
 
-    Ok(())
+    // 遍历是阻塞 I/O，放在独立线程上执行，事件经通道交给异步任务转发给前端
+    let (sender, receiver) = mpsc::channel(delivery::EVENT_CHANNEL_CAPACITY);
+    let acks = if plan.options.ack_batches { Some(acks) } else { None };
+    // 转发完所有事件后才移除会话，在此之前前端仍能确认批次和停止搜索
+    let forward_control = control.clone();
+    let forward_session = session.clone();
+    tauri::async_runtime::spawn(async move {
+        delivery::forward_search_events(app, search_id, forward_session, acks, receiver).await;
+        forward_control.finish(search_id);
+    });
+
+    let handle = thread::Builder::new()
+        .name(format!("search-{}", search_id))
+        .spawn(move || search::run(&sender, &pool, search_id, &session, &plan))
+        .map_err(|e| {
+            control.finish(search_id);
+            format!("Failed to start search: {}", e)
+        })?;
+    control.add_worker(handle);
+
+    Ok(search_id)
 }
 
-fn case_insensitive_eq(a: &String, b: &String, casesense: bool) -> bool {
-    if casesense {
-        a == b
-    } else {
-        a.to_lowercase() == b.to_lowercase()
-    }
+#[command]
+fn get_keep_marker(keep_markers: State<'_, Arc<KeepMarkers>>) -> String {
+    keep_markers.marker()
 }
 
+// 搜索和删除都使用这里设置的保护标记，默认标记始终生效
+#[command]
+fn set_keep_marker(keep_markers: State<'_, Arc<KeepMarkers>>, marker: String) -> Result<(), String> {
+    keep_markers.set(&marker)
+}
 
 #[command]
 async fn delete_folders(
     paths: Vec<String>,
+    keep_markers: State<'_, Arc<KeepMarkers>>,
     state: tauri::State<'_, Arc<Mutex<AppState>>>,
-) -> Result<(), String> {
+) -> Result<Vec<delete::DeleteOutcome>, String> {
+    if paths.is_empty() {
+        return Err("没有接收到任何路径信息".to_string());
+    }
+
     let mut app_state = state.lock().await;
     if app_state.is_deleting {
         return Err("Delete operation is already in progress".to_string());
@@ -141,47 +207,17 @@ async fn delete_folders(
     app_state.is_deleting = true;
     drop(app_state); // Unlock before performing async operations
 
-    if paths.is_empty() {
-        return Err("没有接收到任何路径信息".to_string());
-    }
-
-    for path_str in paths {
-        println!("尝试删除: {}", &path_str);
-        let path = Path::new(&path_str);
-
-        if !path.exists() {
-            println!("路径不存在: {}", path_str);
-            continue;
-        }
-
-        if !path.is_dir() {
-            println!("路径不是一个目录: {}", path_str);
-            continue;
-        }
+    let result = delete::delete_paths(paths, keep_markers.markers()).await;
+    // 出错时也要复位，否则之后的删除都会被拒绝
+    state.lock().await.is_deleting = false;
+    result
+}
 
-        match fs::remove_dir_all(path).await {
-            Ok(_) => {
-                println!("成功删除: {}", path_str);
-                // 验证文件或目录是否已被删除
-                if !Path::new(&path_str).exists() {
-                    println!("验证删除成功: {}", path_str);
-                } else {
-                    let error_msg = format!("验证失败，未删除: {}", path_str);
-                    eprintln!("{}", error_msg);
-                    return Err(error_msg);
-                }
-            }
-            Err(e) => {
-                let error_msg = format!("删除失败: {}，错误: {}", path_str, e);
-                eprintln!("{}", error_msg);
-                return Err(error_msg);
-            }
-        }
-    }
-    let mut app_state = state.lock().await;
-    app_state.is_deleting = false;
-    println!("删除操作完成");
-    Ok(())
+#[command]
+async fn reclaimable_size(paths: Vec<String>) -> Result<size::ReclaimableSize, String> {
+    tauri::async_runtime::spawn_blocking(move || size::reclaimable_size(&paths))
+        .await
+        .map_err(|e| format!("Failed to compute reclaimable size: {}", e))
 }
 
 #[command]
@@ -207,12 +243,21 @@ async fn open_directory(path: String) -> Result<(), String> {
 fn main() {
     let app_state = Arc::new(Mutex::new(AppState { is_deleting: false }));
     let control = Arc::new(SearchControl::new());
+    let keep_markers = Arc::new(KeepMarkers::new());
 
     Builder::default()
         .manage(app_state)
-        .manage(control)
+        .manage(control.clone())
+        .manage(keep_markers)
         .invoke_handler(tauri::generate_handler![search_folders, stop_search, delete_folders,
-            open_directory])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+            open_directory, reclaimable_size, pause_search, resume_search, ack_batch,
+            list_presets, get_keep_marker, set_keep_marker])
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(move |_app, event| {
+            // 窗口关闭时可能仍有搜索在进行，先停止并等待工作线程
+            if let RunEvent::Exit = event {
+                control.shutdown();
+            }
+        });
 }
@@ -0,0 +1,60 @@
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    Exact,
+    Substring,
+    // 通配符，例如 *.egg-info、cmake-build-*
+    Glob,
+    // 正则表达式，在名称中查找，需要整名匹配时自行加 ^$
+    Regex,
+}
+
+// 按模式编译好的名称匹配器，每次搜索只编译一次
+#[derive(Clone, Debug)]
+pub enum NameMatcher {
+    Exact { pattern: String, casesense: bool },
+    Substring { pattern: String, casesense: bool },
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl NameMatcher {
+    pub fn new(pattern: &str, mode: MatchMode, casesense: bool) -> Result<Self, String> {
+        let fold = |s: &str| if casesense { s.to_string() } else { s.to_lowercase() };
+        let matcher = match mode {
+            MatchMode::Exact => NameMatcher::Exact { pattern: fold(pattern), casesense },
+            MatchMode::Substring => NameMatcher::Substring { pattern: fold(pattern), casesense },
+            MatchMode::Glob => {
+                let glob = GlobBuilder::new(pattern)
+                    .case_insensitive(!casesense)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|e| format!("Invalid glob pattern '{}': {}", pattern, e))?;
+                NameMatcher::Glob(glob.compile_matcher())
+            }
+            MatchMode::Regex => {
+                let regex = RegexBuilder::new(pattern)
+                    .case_insensitive(!casesense)
+                    .build()
+                    .map_err(|e| format!("Invalid regex pattern '{}': {}", pattern, e))?;
+                NameMatcher::Regex(regex)
+            }
+        };
+        Ok(matcher)
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        match self {
+            NameMatcher::Exact { pattern, casesense: true } => name == pattern,
+            NameMatcher::Exact { pattern, casesense: false } => name.to_lowercase() == *pattern,
+            NameMatcher::Substring { pattern, casesense: true } => name.contains(pattern.as_str()),
+            NameMatcher::Substring { pattern, casesense: false } => name.to_lowercase().contains(pattern.as_str()),
+            NameMatcher::Glob(glob) => glob.is_match(name),
+            NameMatcher::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
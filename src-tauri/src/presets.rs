@@ -0,0 +1,92 @@
+use crate::matcher::MatchMode;
+use crate::rules::TargetRule;
+use serde::Serialize;
+
+// 内置的清理预设，每个预设是一组经过确认的目标规则
+#[derive(Clone, Debug, Serialize)]
+pub struct Preset {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub rules: Vec<TargetRule>,
+}
+
+// 含通配符的名称按 glob 匹配，其余精确匹配，不受搜索的 matchMode 影响
+fn rule(pattern: &str, markers: &[&str]) -> TargetRule {
+    let mode = if pattern.contains('*') { MatchMode::Glob } else { MatchMode::Exact };
+    TargetRule {
+        pattern: pattern.to_string(),
+        markers: markers.iter().map(|m| m.to_string()).collect(),
+        mode: Some(mode),
+    }
+}
+
+const GRADLE_MARKERS: &[&str] = &["build.gradle", "build.gradle.kts", "settings.gradle", "settings.gradle.kts"];
+const DOTNET_MARKERS: &[&str] = &["*.csproj", "*.fsproj", "*.vbproj"];
+
+pub fn presets() -> Vec<Preset> {
+    vec![
+        Preset {
+            id: "rust",
+            name: "Rust",
+            rules: vec![rule("target", &["Cargo.toml"])],
+        },
+        Preset {
+            id: "node",
+            name: "Node.js",
+            rules: vec![
+                rule("node_modules", &["package.json"]),
+                rule(".next", &["package.json"]),
+                rule(".nuxt", &["package.json"]),
+                rule(".parcel-cache", &["package.json"]),
+                rule(".turbo", &["package.json"]),
+            ],
+        },
+        Preset {
+            id: "python",
+            name: "Python",
+            rules: vec![
+                rule("__pycache__", &[]),
+                rule(".venv", &[]),
+                rule(".tox", &[]),
+                rule(".pytest_cache", &[]),
+                rule(".mypy_cache", &[]),
+                rule("*.egg-info", &[]),
+            ],
+        },
+        Preset {
+            id: "jvm",
+            name: "JVM (Gradle / Maven)",
+            rules: vec![
+                rule(".gradle", GRADLE_MARKERS),
+                rule("build", GRADLE_MARKERS),
+                rule("target", &["pom.xml"]),
+            ],
+        },
+        Preset {
+            id: "dotnet",
+            name: ".NET",
+            rules: vec![rule("bin", DOTNET_MARKERS), rule("obj", DOTNET_MARKERS)],
+        },
+        Preset {
+            id: "cmake",
+            name: "CMake",
+            rules: vec![
+                rule("build", &["CMakeLists.txt"]),
+                rule("cmake-build-*", &["CMakeLists.txt"]),
+            ],
+        },
+        Preset {
+            id: "xcode",
+            name: "Xcode / Swift",
+            rules: vec![
+                rule("DerivedData", &[]),
+                rule("build", &["*.xcodeproj", "*.xcworkspace"]),
+                rule(".build", &["Package.swift"]),
+            ],
+        },
+    ]
+}
+
+pub fn find_preset(id: &str) -> Option<Preset> {
+    presets().into_iter().find(|p| p.id == id)
+}
@@ -0,0 +1,199 @@
+use crate::matcher::{MatchMode, NameMatcher};
+use globset::{GlobBuilder, GlobMatcher};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+// 目标规则：只写名称时按名称匹配；带 markers 时要求父目录里存在其中任一标记文件
+// 例如 target + Cargo.toml、node_modules + package.json
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(from = "TargetRuleDef")]
+pub struct TargetRule {
+    pub pattern: String,
+    pub markers: Vec<String>,
+    // 规则自己的匹配方式，不填时使用搜索的 matchMode
+    pub mode: Option<MatchMode>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TargetRuleDef {
+    Name(String),
+    Rule {
+        pattern: String,
+        #[serde(default)]
+        markers: Vec<String>,
+        #[serde(default)]
+        mode: Option<MatchMode>,
+    },
+}
+
+impl From<TargetRuleDef> for TargetRule {
+    fn from(def: TargetRuleDef) -> Self {
+        match def {
+            TargetRuleDef::Name(pattern) => TargetRule { pattern, markers: Vec::new(), mode: None },
+            TargetRuleDef::Rule { pattern, markers, mode } => TargetRule { pattern, markers, mode },
+        }
+    }
+}
+
+// 目标匹配的条目类型
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TargetKind {
+    #[default]
+    Dirs,
+    // 普通文件，例如 .DS_Store、Thumbs.db、*.log
+    Files,
+    Both,
+}
+
+impl TargetKind {
+    pub fn dirs(self) -> bool {
+        self != TargetKind::Files
+    }
+
+    pub fn files(self) -> bool {
+        self != TargetKind::Dirs
+    }
+}
+
+enum Marker {
+    // 普通文件名直接检查是否存在
+    Name(String),
+    // 含通配符的标记（如 *.csproj）需要列出父目录
+    Glob(NameMatcher),
+}
+
+// 编译好的目标规则
+pub struct Target {
+    pub pattern: String,
+    pub matcher: NameMatcher,
+    pub digin: bool,
+    markers: Vec<Marker>,
+}
+
+impl Target {
+    pub fn new(rule: &TargetRule, mode: MatchMode, casesense: bool, digin: bool) -> Result<Self, String> {
+        let mut markers = Vec::new();
+        for marker in rule.markers.iter().filter(|m| !m.is_empty()) {
+            if marker.contains(['*', '?', '[', '{']) {
+                markers.push(Marker::Glob(NameMatcher::new(marker, MatchMode::Glob, true)?));
+            } else {
+                markers.push(Marker::Name(marker.clone()));
+            }
+        }
+
+        Ok(Self {
+            pattern: rule.pattern.clone(),
+            matcher: NameMatcher::new(&rule.pattern, rule.mode.unwrap_or(mode), casesense)?,
+            digin,
+            markers,
+        })
+    }
+
+    // 名称匹配且标记条件满足时返回 Some，内层为确认匹配的标记文件名（无标记要求时为 None）
+    pub fn confirm(&self, name: &str, parent: &Path) -> Option<Option<String>> {
+        if !self.matcher.is_match(name) {
+            return None;
+        }
+        if self.markers.is_empty() {
+            return Some(None);
+        }
+        self.find_marker(parent).map(Some)
+    }
+
+    fn find_marker(&self, parent: &Path) -> Option<String> {
+        for marker in &self.markers {
+            if let Marker::Name(name) = marker {
+                if parent.join(name).exists() {
+                    return Some(name.clone());
+                }
+            }
+        }
+
+        let globs: Vec<&NameMatcher> = self
+            .markers
+            .iter()
+            .filter_map(|m| match m {
+                Marker::Glob(glob) => Some(glob),
+                Marker::Name(_) => None,
+            })
+            .collect();
+        if globs.is_empty() {
+            return None;
+        }
+        fs::read_dir(parent)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .find(|name| globs.iter().any(|glob| glob.is_match(name)))
+    }
+}
+
+enum SkipPattern {
+    // 绝对路径，或相对所在搜索根目录的路径
+    Path(PathBuf),
+    // 通配符，同时与完整路径和相对根目录的路径比较
+    Glob(GlobMatcher),
+}
+
+// 按路径跳过的规则，例如 /home/me/work/legacy、work/legacy、**/legacy/cache
+pub struct SkipRule {
+    pub rule: String,
+    pattern: SkipPattern,
+    casesense: bool,
+}
+
+impl SkipRule {
+    pub fn new(rule: &str, casesense: bool) -> Result<Self, String> {
+        let pattern = if rule.contains(['*', '?', '[', '{']) {
+            let glob = GlobBuilder::new(rule)
+                .case_insensitive(!casesense)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| format!("Invalid skip rule '{}': {}", rule, e))?;
+            SkipPattern::Glob(glob.compile_matcher())
+        } else {
+            // 去掉 ./，相对路径在匹配时与所在根目录下的相对路径比较
+            let path: PathBuf = Path::new(rule)
+                .components()
+                .filter(|c| *c != Component::CurDir)
+                .collect();
+            SkipPattern::Path(path)
+        };
+        Ok(Self {
+            rule: rule.to_string(),
+            pattern,
+            casesense,
+        })
+    }
+
+    pub fn is_match(&self, path: &Path, root: &Path) -> bool {
+        match &self.pattern {
+            SkipPattern::Path(skip) => {
+                let path = if skip.is_absolute() {
+                    path
+                } else {
+                    match path.strip_prefix(root) {
+                        Ok(relative) => relative,
+                        Err(_) => return false,
+                    }
+                };
+                if self.casesense {
+                    return path == skip;
+                }
+                // 按组件比较，忽略多余的分隔符和末尾的 /
+                let components = |p: &Path| {
+                    p.components()
+                        .map(|c| c.as_os_str().to_string_lossy().to_lowercase())
+                        .collect::<Vec<_>>()
+                };
+                components(path) == components(skip)
+            }
+            SkipPattern::Glob(glob) => {
+                glob.is_match(path) || path.strip_prefix(root).is_ok_and(|relative| glob.is_match(relative))
+            }
+        }
+    }
+}
@@ -0,0 +1,965 @@
+use crate::git::{GitStatus, StatusCache};
+use crate::ignore_rules::{IgnoreChecker, IgnoreLayer, ProtectReason};
+use crate::keep;
+use crate::matcher::MatchMode;
+use crate::presets;
+use crate::rules::{SkipRule, Target, TargetKind, TargetRule};
+use crate::size;
+use crate::staleness::{self, ActivityCache, StalenessSource};
+use rayon::{Scope, ThreadPool, ThreadPoolBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs::{self, DirEntry, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+use tokio::sync::mpsc::Sender;
+
+pub type SearchId = u64;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchOptions {
+    #[serde(default)]
+    pub path: String,
+    // 额外的搜索根目录，与 path 一起搜索；位于另一个根目录之下的根目录也单独遍历，其中的匹配记在最内层的根目录下
+    #[serde(default)]
+    pub roots: Vec<String>,
+    // 要查找的目标，一次遍历同时匹配所有目标
+    #[serde(default)]
+    pub targets: Vec<TargetRule>,
+    // 目标匹配目录、文件还是两者都匹配，跳过规则对文件同样生效
+    #[serde(default)]
+    pub target_kind: TargetKind,
+    // 内置预设 id，预设的规则会追加到 targets 之后
+    #[serde(default)]
+    pub preset: Option<String>,
+    #[serde(default)]
+    pub skipfolders: Vec<String>,
+    // 按路径跳过：绝对路径、相对所在搜索根目录的路径或完整路径上的通配符
+    #[serde(default)]
+    pub skip_paths: Vec<String>,
+    #[serde(default)]
+    pub digin: bool,
+    // 旧的模糊匹配开关，未指定 matchMode 时等同于 substring
+    #[serde(default)]
+    pub fuzzy: bool,
+    #[serde(default)]
+    pub match_mode: Option<MatchMode>,
+    #[serde(default)]
+    pub casesense: bool,
+    // 只保留至少这么多天没有改动的项目里的匹配，填写后每个匹配都会带上 last_activity
+    #[serde(default)]
+    pub min_idle_days: Option<u64>,
+    // 只报告占用空间至少这么多字节的匹配
+    #[serde(default)]
+    pub min_size: Option<u64>,
+    // 只报告至少包含这么多文件的匹配
+    #[serde(default)]
+    pub min_files: Option<u64>,
+    // 为 git 时每个匹配都会带上所在仓库的提交时间和改动状态
+    #[serde(default)]
+    pub staleness_source: StalenessSource,
+    // 只报告被所在仓库忽略且未被跟踪的匹配，其余作为受保护的匹配单独报告
+    #[serde(default)]
+    pub gitignored_only: bool,
+    // 不深入 .gitignore、.ignore、.foldermanageignore 忽略的目录；被忽略的目录本身仍会参与匹配，
+    // 所以 node_modules 这类通常被忽略的目标照样能找到
+    #[serde(default)]
+    pub respect_ignore_files: bool,
+    // 最大深度，根目录的直接子目录为 1，更深的目录不再读取
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    // 比此深度浅的目录不作为结果，但仍会深入查找
+    #[serde(default)]
+    pub min_depth: Option<usize>,
+    // 不进入其他文件系统上的目录（挂载点），跳过的挂载点通过 mount-point-skipped 报告；仅 unix 有效
+    #[serde(default)]
+    pub same_filesystem: bool,
+    // 把指向目录的符号链接当作目录匹配和深入，指回上级目录的链接作为 search-warning 报告且不深入
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    // 遍历线程数，不填时按 CPU 核数
+    #[serde(default)]
+    pub threads: Option<usize>,
+    // 前端是否会用 ack_batch 确认收到的结果批次，开启后未确认的批次过多时暂停推送
+    #[serde(default)]
+    pub ack_batches: bool,
+}
+
+// 单次搜索的控制状态，每个搜索有独立的取消和暂停标志
+pub struct SearchSession {
+    should_continue: AtomicBool,
+    paused: Mutex<bool>,
+    resumed: Condvar,
+}
+
+impl SearchSession {
+    pub fn new() -> Self {
+        Self {
+            should_continue: AtomicBool::new(true),
+            paused: Mutex::new(false),
+            resumed: Condvar::new(),
+        }
+    }
+
+    pub fn stop(&self) {
+        self.should_continue.store(false, Ordering::Relaxed);
+        // 唤醒暂停中的遍历，让它看到停止标志
+        self.resume();
+    }
+
+    pub fn pause(&self) {
+        *self.paused.lock().unwrap() = true;
+    }
+
+    pub fn resume(&self) {
+        *self.paused.lock().unwrap() = false;
+        self.resumed.notify_all();
+    }
+
+    pub fn continue_search(&self) -> bool {
+        self.should_continue.load(Ordering::Relaxed)
+    }
+
+    // 暂停时阻塞当前线程，遍历位置保留在迭代器中，恢复后原地继续
+    // 返回是否发生过等待
+    pub fn wait_if_paused(&self) -> bool {
+        let mut paused = self.paused.lock().unwrap();
+        let mut waited = false;
+        while *paused && self.continue_search() {
+            waited = true;
+            paused = self.resumed.wait(paused).unwrap();
+        }
+        waited
+    }
+}
+
+// folder-found / skip-folder-found 事件的载荷
+#[derive(Clone, Debug, Serialize)]
+pub struct MatchRecord {
+    pub search_id: SearchId,
+    pub path: String,
+    pub root: String,
+    // 相对搜索根目录的层级，根目录的直接子目录为 1
+    pub depth: usize,
+    // 修改时间，Unix 秒
+    pub mtime: Option<u64>,
+    pub is_symlink: bool,
+    // 匹配到的是文件而不是目录
+    pub is_file: bool,
+    // 命中的规则（目标名称或跳过的文件夹名）
+    pub rule: String,
+    // 确认匹配的标记文件（规则要求标记时）
+    pub marker: Option<String>,
+    // 所在项目最后活动时间，Unix 秒，仅在设置了 minIdleDays 时计算
+    pub last_activity: Option<u64>,
+    // 所在 git 仓库的状态，仅在 stalenessSource 为 git 且位于仓库中时提供
+    pub git: Option<GitStatus>,
+}
+
+impl MatchRecord {
+    pub fn from_entry(search_id: SearchId, root: &str, entry: &DirEntry, depth: usize, rule: &str) -> Self {
+        Self::from_metadata(search_id, root, &entry.path(), entry.metadata().ok(), depth, rule)
+    }
+
+    pub fn from_path(search_id: SearchId, root: &str, path: &Path, depth: usize, rule: &str) -> Self {
+        Self::from_metadata(search_id, root, path, fs::symlink_metadata(path).ok(), depth, rule)
+    }
+
+    fn from_metadata(
+        search_id: SearchId,
+        root: &str,
+        path: &Path,
+        metadata: Option<Metadata>,
+        depth: usize,
+        rule: &str,
+    ) -> Self {
+        let mtime = metadata
+            .as_ref()
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+
+        Self {
+            search_id,
+            path: path.display().to_string(),
+            root: root.to_string(),
+            depth,
+            mtime,
+            is_symlink: metadata.is_some_and(|m| m.file_type().is_symlink()),
+            is_file: false,
+            rule: rule.to_string(),
+            marker: None,
+            last_activity: None,
+            git: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    // 名称与 skipfolders 中的一项相同
+    SkipFolder,
+    // 路径命中 skipPaths 中的一条规则
+    SkipPath,
+    // 目录本身或其下的目录中有保护标记文件
+    KeepMarker,
+}
+
+// skip-folder-found 事件的载荷，rule 为命中的跳过名称、路径规则或标记文件名
+#[derive(Clone, Debug, Serialize)]
+pub struct SkippedRecord {
+    #[serde(flatten)]
+    pub record: MatchRecord,
+    pub reason: SkipReason,
+    // 因保护标记跳过时，含有标记文件的目录（可能是匹配目录下的某个目录）
+    pub protected: Option<String>,
+}
+
+// protected-folder-found 事件的载荷，开启 gitignoredOnly 时不能删除的匹配
+#[derive(Clone, Debug, Serialize)]
+pub struct ProtectedRecord {
+    #[serde(flatten)]
+    pub record: MatchRecord,
+    pub reason: ProtectReason,
+}
+
+// mount-point-skipped 事件的载荷
+#[derive(Clone, Debug, Serialize)]
+pub struct MountPointSkipped {
+    pub search_id: SearchId,
+    pub path: String,
+    pub depth: usize,
+    // 挂载点所在文件系统的设备号
+    pub device: u64,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningKind {
+    // 符号链接指向正在遍历的上级目录
+    SymlinkLoop,
+}
+
+// search-warning 事件的载荷，路径本身可以匹配，但没有深入
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchWarning {
+    pub search_id: SearchId,
+    pub path: String,
+    pub depth: usize,
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WalkErrorKind {
+    PermissionDenied,
+    NotFound,
+    Io,
+}
+
+// search-error 事件的载荷，遍历中无法读取的路径
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchError {
+    pub search_id: SearchId,
+    pub path: Option<String>,
+    pub depth: usize,
+    pub kind: WalkErrorKind,
+    pub message: String,
+}
+
+impl SearchError {
+    fn from_io_error(search_id: SearchId, path: &Path, depth: usize, err: &io::Error) -> Self {
+        let kind = match err.kind() {
+            io::ErrorKind::PermissionDenied => WalkErrorKind::PermissionDenied,
+            io::ErrorKind::NotFound => WalkErrorKind::NotFound,
+            _ => WalkErrorKind::Io,
+        };
+
+        Self {
+            search_id,
+            path: Some(path.display().to_string()),
+            depth,
+            kind,
+            message: err.to_string(),
+        }
+    }
+}
+
+// no-folders-found 事件的载荷，path 为所有搜索根目录
+#[derive(Clone, Debug, Serialize)]
+pub struct NoneFound {
+    pub search_id: SearchId,
+    pub path: String,
+}
+
+// search-progress 事件的载荷
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchProgress {
+    pub search_id: SearchId,
+    pub dirs_visited: u64,
+    pub entries_visited: u64,
+    pub entries_per_sec: f64,
+    pub errors: u64,
+    pub current_dir: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TargetCount {
+    pub target: String,
+    pub count: usize,
+}
+
+// search-finished 事件的载荷，搜索结束（完成或被停止）时发送一次
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchFinished {
+    pub search_id: SearchId,
+    pub count: usize,
+    pub target_counts: Vec<TargetCount>,
+    // 因 gitignoredOnly 被保护、未计入 count 的匹配数
+    pub protected: u64,
+    // 因 sameFilesystem 未进入的挂载点数
+    pub mount_points_skipped: u64,
+    // 小于 minSize 或 minFiles、未计入 count 的匹配数
+    pub below_threshold: u64,
+    pub dirs_visited: u64,
+    pub entries_visited: u64,
+    pub errors: u64,
+    pub warnings: u64,
+    pub elapsed_ms: u64,
+    pub cancelled: bool,
+}
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+// 统计遍历进度，多个遍历线程共享，按时间间隔节流发送 search-progress
+struct ProgressTracker {
+    search_id: SearchId,
+    started: Instant,
+    // (上次发送时间, 上次发送时的条目数)
+    last_emit: Mutex<(Instant, u64)>,
+    dirs_visited: AtomicU64,
+    entries_visited: AtomicU64,
+    errors: AtomicU64,
+    warnings: AtomicU64,
+    protected: AtomicU64,
+    mount_points_skipped: AtomicU64,
+    below_threshold: AtomicU64,
+}
+
+impl ProgressTracker {
+    fn new(search_id: SearchId) -> Self {
+        let now = Instant::now();
+        Self {
+            search_id,
+            started: now,
+            last_emit: Mutex::new((now, 0)),
+            dirs_visited: AtomicU64::new(0),
+            entries_visited: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            warnings: AtomicU64::new(0),
+            protected: AtomicU64::new(0),
+            mount_points_skipped: AtomicU64::new(0),
+            below_threshold: AtomicU64::new(0),
+        }
+    }
+
+    // 暂停恢复后重新计时，避免暂停时间拉低速率
+    fn restart_interval(&self) {
+        *self.last_emit.lock().unwrap() = (Instant::now(), self.entries_visited.load(Ordering::Relaxed));
+    }
+
+    fn maybe_emit(&self, events: &EventSender, current_dir: &Path) {
+        // 其他线程正在发送时直接跳过
+        let mut last_emit = match self.last_emit.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => return,
+        };
+        let elapsed = last_emit.0.elapsed();
+        if elapsed < PROGRESS_INTERVAL {
+            return;
+        }
+        let entries_visited = self.entries_visited.load(Ordering::Relaxed);
+        let progress = SearchProgress {
+            search_id: self.search_id,
+            dirs_visited: self.dirs_visited.load(Ordering::Relaxed),
+            entries_visited,
+            entries_per_sec: entries_visited.saturating_sub(last_emit.1) as f64 / elapsed.as_secs_f64(),
+            errors: self.errors.load(Ordering::Relaxed),
+            current_dir: current_dir.display().to_string(),
+        };
+        let _ = events.blocking_send(SearchEvent::Progress(progress));
+        *last_emit = (Instant::now(), entries_visited);
+    }
+
+    fn finish(&self, target_counts: Vec<TargetCount>, cancelled: bool) -> SearchFinished {
+        SearchFinished {
+            search_id: self.search_id,
+            count: target_counts.iter().map(|t| t.count).sum(),
+            target_counts,
+            protected: self.protected.load(Ordering::Relaxed),
+            mount_points_skipped: self.mount_points_skipped.load(Ordering::Relaxed),
+            below_threshold: self.below_threshold.load(Ordering::Relaxed),
+            dirs_visited: self.dirs_visited.load(Ordering::Relaxed),
+            entries_visited: self.entries_visited.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            warnings: self.warnings.load(Ordering::Relaxed),
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+            cancelled,
+        }
+    }
+}
+
+// 遍历线程发给命令层的事件，由命令层转发到前端
+#[derive(Clone, Debug)]
+pub enum SearchEvent {
+    Found(MatchRecord),
+    Skipped(SkippedRecord),
+    Protected(ProtectedRecord),
+    MountPointSkipped(MountPointSkipped),
+    Size(size::FolderSize),
+    Progress(SearchProgress),
+    Error(SearchError),
+    Warning(SearchWarning),
+    NoneFound(NoneFound),
+    Finished(SearchFinished),
+}
+
+// 有界通道：前端处理不过来时遍历线程会在发送处阻塞
+pub type EventSender = Sender<SearchEvent>;
+
+// 每个搜索使用独立的线程池，目录作为任务在线程间窃取执行
+pub fn build_pool(search_id: SearchId, options: &SearchOptions) -> Result<ThreadPool, String> {
+    ThreadPoolBuilder::new()
+        .num_threads(options.threads.unwrap_or(0))
+        .thread_name(move |i| format!("search-{}-{}", search_id, i))
+        .build()
+        .map_err(|e| format!("Failed to start search threads: {}", e))
+}
+
+// 搜索开始前编译好的规则，非法的模式在启动搜索时就报错
+pub struct SearchPlan {
+    pub options: SearchOptions,
+    // 去重后的搜索根目录，保持原来的写法
+    roots: Vec<String>,
+    targets: Vec<Target>,
+    skip_paths: Vec<SkipRule>,
+    // 含有其中任一文件的目录受保护，不作为结果也不再深入
+    keep_markers: Vec<String>,
+}
+
+impl SearchPlan {
+    pub fn new(options: SearchOptions, keep_markers: Vec<String>) -> Result<Self, String> {
+        let mode = options.match_mode.unwrap_or(if options.fuzzy {
+            MatchMode::Substring
+        } else {
+            MatchMode::Exact
+        });
+        let mut rules = options.targets.clone();
+        if let Some(id) = &options.preset {
+            let preset = presets::find_preset(id).ok_or_else(|| format!("Unknown preset: {}", id))?;
+            rules.extend(preset.rules);
+        }
+        if options.max_depth == Some(0) {
+            return Err("maxDepth must be at least 1".to_string());
+        }
+        if let (Some(min_depth), Some(max_depth)) = (options.min_depth, options.max_depth) {
+            if min_depth > max_depth {
+                return Err(format!("minDepth ({}) is greater than maxDepth ({})", min_depth, max_depth));
+            }
+        }
+        let roots = dedup_roots(&options);
+        if roots.is_empty() {
+            return Err("No search path given".to_string());
+        }
+        if rules.iter().all(|t| t.pattern.is_empty()) {
+            return Err("No target folder name given".to_string());
+        }
+
+        let mut targets = Vec::new();
+        for rule in rules.iter().filter(|t| !t.pattern.is_empty()) {
+            // 如果任一 skipfolder 与目标名称相等，不再深入匹配到的目录
+            let digin = options.digin
+                && !options
+                    .skipfolders
+                    .iter()
+                    .any(|s| case_insensitive_eq(s, &rule.pattern, options.casesense));
+            targets.push(Target::new(rule, mode, options.casesense, digin)?);
+        }
+
+        let skip_paths = options
+            .skip_paths
+            .iter()
+            .filter(|rule| !rule.is_empty())
+            .map(|rule| SkipRule::new(rule, options.casesense))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            options,
+            roots,
+            targets,
+            skip_paths,
+            keep_markers,
+        })
+    }
+}
+
+// path 和 roots 中的根目录按真实路径去重，互相包含的根目录都保留
+fn dedup_roots(options: &SearchOptions) -> Vec<String> {
+    let mut seen = HashSet::new();
+    std::iter::once(&options.path)
+        .chain(&options.roots)
+        .filter(|root| !root.is_empty())
+        .filter(|root| seen.insert(fs::canonicalize(root).unwrap_or_else(|_| PathBuf::from(root))))
+        .cloned()
+        .collect()
+}
+
+// 开启 followSymlinks 时从根目录到当前目录的真实路径链
+struct RealDir {
+    path: PathBuf,
+    parent: Option<Arc<RealDir>>,
+}
+
+impl RealDir {
+    fn contains(&self, path: &Path) -> bool {
+        let mut dir = Some(self);
+        while let Some(current) = dir {
+            if current.path == path {
+                return true;
+            }
+            dir = current.parent.as_deref();
+        }
+        false
+    }
+}
+
+// 目录任务从上级目录继承的状态
+#[derive(Clone, Default)]
+struct DirContext {
+    // 所在根目录在 Walk::roots 中的下标
+    root: usize,
+    // 上级目录累积的忽略规则，未开启 respectIgnoreFiles 时为 None
+    ignores: Option<Arc<IgnoreLayer>>,
+    // 用于发现符号链接循环，未开启 followSymlinks 时为 None
+    ancestors: Option<Arc<RealDir>>,
+}
+
+// 一次搜索在所有遍历线程间共享的状态
+struct Walk<'a> {
+    events: &'a EventSender,
+    search_id: SearchId,
+    session: &'a SearchSession,
+    plan: &'a SearchPlan,
+    // 每个目标各自的匹配数，下标与 plan.targets 对应
+    counts: Vec<AtomicUsize>,
+    progress: ProgressTracker,
+    roots: Vec<Root>,
+    git_status: StatusCache,
+    activity: ActivityCache,
+    ignore_checker: IgnoreChecker,
+}
+
+struct Root {
+    path: String,
+    // 根目录的真实路径，用于在其他根目录的遍历中认出它
+    real_path: PathBuf,
+    // 开启 sameFilesystem 时根目录所在的设备
+    device: Option<u64>,
+}
+
+// 在当前线程上阻塞执行整个搜索，结果通过 events 发出
+// 每个匹配的大小在发出前统计好，最后发送的 Finished 是这次搜索的最后一个事件
+pub fn run(
+    events: &EventSender,
+    pool: &ThreadPool,
+    search_id: SearchId,
+    session: &Arc<SearchSession>,
+    plan: &SearchPlan,
+) {
+    let options = &plan.options;
+    let roots = plan
+        .roots
+        .iter()
+        .map(|path| Root {
+            path: path.clone(),
+            real_path: fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path)),
+            device: if options.same_filesystem {
+                fs::metadata(path).ok().and_then(|m| device_id(&m))
+            } else {
+                None
+            },
+        })
+        .collect();
+    let walk = Walk {
+        events,
+        search_id,
+        session,
+        plan,
+        counts: plan.targets.iter().map(|_| AtomicUsize::new(0)).collect(),
+        progress: ProgressTracker::new(search_id),
+        roots,
+        git_status: StatusCache::default(),
+        activity: ActivityCache::default(),
+        ignore_checker: IgnoreChecker::default(),
+    };
+
+    // 各根目录在同一个线程池中并行遍历
+    pool.scope(|scope| {
+        for (index, root) in plan.roots.iter().enumerate() {
+            let root = PathBuf::from(root);
+            let context = DirContext {
+                root: index,
+                ignores: options.respect_ignore_files.then(|| IgnoreLayer::for_root(&root)),
+                ancestors: options.follow_symlinks.then(|| {
+                    Arc::new(RealDir {
+                        path: fs::canonicalize(&root).unwrap_or_else(|_| root.clone()),
+                        parent: None,
+                    })
+                }),
+            };
+            let walk = &walk;
+            scope.spawn(move |scope| walk.visit_dir(scope, root, 0, context));
+        }
+    });
+
+    let target_counts: Vec<TargetCount> = plan
+        .targets
+        .iter()
+        .zip(&walk.counts)
+        .map(|(target, count)| TargetCount {
+            target: target.pattern.clone(),
+            count: count.load(Ordering::Relaxed),
+        })
+        .collect();
+    // 检查是否有文件夹被找到
+    if target_counts.iter().all(|t| t.count == 0) {
+        let none_found = NoneFound {
+            search_id,
+            path: plan.roots.join(", "),
+        };
+        let _ = events.blocking_send(SearchEvent::NoneFound(none_found));
+    }
+
+    let finished = walk.progress.finish(target_counts, !session.continue_search());
+    let _ = events.blocking_send(SearchEvent::Finished(finished));
+}
+
+impl<'a> Walk<'a> {
+    // 读取一个目录，子目录作为新任务交给线程池，depth 为该目录相对根目录的层级
+    fn visit_dir<'s>(&'s self, scope: &Scope<'s>, dir: PathBuf, depth: usize, mut context: DirContext) {
+        if self.session.wait_if_paused() {
+            self.progress.restart_interval();
+        }
+        if !self.session.continue_search() {
+            return;
+        }
+        self.progress.maybe_emit(self.events, &dir);
+
+        // 先读完整个目录，才能在处理子目录之前发现保护标记
+        let entries: Vec<io::Result<DirEntry>> = match fs::read_dir(&dir) {
+            Ok(read_dir) => read_dir.collect(),
+            Err(err) => return self.report_error(&dir, depth, &err),
+        };
+        let marker = entries
+            .iter()
+            .filter_map(|entry| entry.as_ref().ok())
+            .find(|entry| keep::is_keep_marker(&entry.file_name(), &self.plan.keep_markers));
+        if let Some(marker) = marker.filter(|_| depth > 0) {
+            let root = &self.roots[context.root].path;
+            let rule = marker.file_name().to_string_lossy().to_string();
+            let record = MatchRecord::from_path(self.search_id, root, &dir, depth, &rule);
+            return self.report_skipped(record, SkipReason::KeepMarker, Some(&dir));
+        }
+        // 根目录的规则在开始时已经加载
+        if depth > 0 {
+            context.ignores = context.ignores.map(|layer| layer.child(&dir));
+        }
+
+        for entry in entries {
+            if !self.session.continue_search() {
+                return;
+            }
+            self.progress.entries_visited.fetch_add(1, Ordering::Relaxed);
+
+            let entry = match entry {
+                Ok(e) => e,
+                Err(err) => {
+                    self.report_error(&dir, depth + 1, &err);
+                    continue;
+                }
+            };
+            // DirEntry::file_type 不跟随符号链接
+            let file_type = match entry.file_type() {
+                Ok(t) => t,
+                Err(err) => {
+                    self.report_error(&entry.path(), depth + 1, &err);
+                    continue;
+                }
+            };
+            // 跟随符号链接时取链接目标的信息，已失效的链接不处理
+            let is_symlink = file_type.is_symlink();
+            let target = if file_type.is_dir() {
+                None
+            } else if is_symlink && self.plan.options.follow_symlinks {
+                match fs::metadata(entry.path()) {
+                    Ok(metadata) if metadata.is_dir() => Some(metadata),
+                    Ok(metadata) if metadata.is_file() => {
+                        self.visit_file(&entry, depth + 1, &context);
+                        continue;
+                    }
+                    _ => continue,
+                }
+            } else {
+                if file_type.is_file() {
+                    self.visit_file(&entry, depth + 1, &context);
+                }
+                continue;
+            };
+            self.progress.dirs_visited.fetch_add(1, Ordering::Relaxed);
+
+            if let Some(root_device) = self.roots[context.root].device {
+                let device = target.or_else(|| entry.metadata().ok()).and_then(|m| device_id(&m));
+                if let Some(device) = device.filter(|device| *device != root_device) {
+                    self.progress.mount_points_skipped.fetch_add(1, Ordering::Relaxed);
+                    let skipped = MountPointSkipped {
+                        search_id: self.search_id,
+                        path: entry.path().display().to_string(),
+                        depth: depth + 1,
+                        device,
+                    };
+                    let _ = self.events.blocking_send(SearchEvent::MountPointSkipped(skipped));
+                    continue;
+                }
+            }
+
+            // 先匹配再剪枝，被忽略的目录本身仍可能是结果
+            if self.visit_folder(&entry, depth + 1, &context) {
+                // 子目录的内容位于 depth + 2，超过最大深度时不再读取
+                if self.plan.options.max_depth.is_some_and(|max_depth| depth + 2 > max_depth) {
+                    continue;
+                }
+                let path = entry.path();
+                if context.ignores.as_ref().is_some_and(|layer| layer.is_ignored(&path, true)) {
+                    continue;
+                }
+                // 另一个根目录由它自己的任务遍历，这里不再深入，其中的匹配都记在最内层的根目录下
+                if self.is_other_root(&path, &context) {
+                    continue;
+                }
+                let ancestors = match &context.ancestors {
+                    Some(ancestors) => match self.real_dir(ancestors, &path, is_symlink, depth + 1) {
+                        Some(real_dir) => Some(real_dir),
+                        None => continue,
+                    },
+                    None => None,
+                };
+                let context = DirContext {
+                    root: context.root,
+                    ignores: context.ignores.clone(),
+                    ancestors,
+                };
+                scope.spawn(move |scope| self.visit_dir(scope, path, depth + 1, context));
+            }
+        }
+    }
+
+    // 处理一个子目录，返回是否需要继续深入
+    fn visit_folder(&self, entry: &DirEntry, depth: usize, context: &DirContext) -> bool {
+        let SearchOptions { skipfolders, casesense, min_depth, target_kind, .. } = &self.plan.options;
+        let path = &self.roots[context.root].path;
+        let current_folder_name = entry.file_name().to_string_lossy().to_string();
+
+        if let Some(skipfolder) = skipfolders.iter().find(|s| case_insensitive_eq(s, &current_folder_name, *casesense)) {
+            let record = MatchRecord::from_entry(self.search_id, path, entry, depth, skipfolder);
+            self.report_skipped(record, SkipReason::SkipFolder, None);
+            return false;
+        }
+        let root = Path::new(path);
+        if let Some(skip) = self.plan.skip_paths.iter().find(|skip| skip.is_match(&entry.path(), root)) {
+            let record = MatchRecord::from_entry(self.search_id, path, entry, depth, &skip.rule);
+            self.report_skipped(record, SkipReason::SkipPath, None);
+            return false;
+        }
+
+        if min_depth.is_some_and(|min_depth| depth < min_depth) || !target_kind.dirs() {
+            return true;
+        }
+        self.match_target(entry, &current_folder_name, depth, path, true).unwrap_or(true)
+    }
+
+    // 处理一个文件，只在 targetKind 包含文件时匹配
+    fn visit_file(&self, entry: &DirEntry, depth: usize, context: &DirContext) {
+        // 保护标记文件本身不作为结果
+        let is_marker = keep::is_keep_marker(&entry.file_name(), &self.plan.keep_markers);
+        if !self.plan.options.target_kind.files() || is_marker {
+            return;
+        }
+        let file_name = entry.file_name().to_string_lossy().to_string();
+        let path = &self.roots[context.root].path;
+        if let Some(skip) = self.plan.skip_paths.iter().find(|skip| skip.is_match(&entry.path(), Path::new(path))) {
+            let mut record = MatchRecord::from_entry(self.search_id, path, entry, depth, &skip.rule);
+            record.is_file = true;
+            return self.report_skipped(record, SkipReason::SkipPath, None);
+        }
+        if self.plan.options.min_depth.is_some_and(|min_depth| depth < min_depth) {
+            return;
+        }
+        self.match_target(entry, &file_name, depth, path, false);
+    }
+
+    // 按顺序取第一个名称和标记都满足的目标并报告，没有目标匹配时返回 None，否则返回是否需要继续深入
+    fn match_target(&self, entry: &DirEntry, name: &str, depth: usize, path: &str, is_dir: bool) -> Option<bool> {
+        let parent = entry.path().parent().map(Path::to_path_buf).unwrap_or_default();
+        let (index, marker) = self
+            .plan
+            .targets
+            .iter()
+            .enumerate()
+            .find_map(|(index, target)| target.confirm(name, &parent).map(|marker| (index, marker)))?;
+
+        let target = &self.plan.targets[index];
+        let mut record = MatchRecord::from_entry(self.search_id, path, entry, depth, &target.pattern);
+        record.is_file = !is_dir;
+        record.marker = marker;
+
+        let should_continue = || self.session.continue_search();
+        if self.plan.options.staleness_source == StalenessSource::Git {
+            record.git = self.git_status.status(&parent, should_continue);
+        }
+        if let Some(min_idle_days) = self.plan.options.min_idle_days {
+            record.last_activity = match record.git.as_ref().and_then(|git| git.commit_time) {
+                Some(commit_time) => Some(commit_time),
+                None => {
+                    // 项目中任一层级上名称匹配目标的条目都不算活动，例如多个 *.log、子包里的 node_modules
+                    let is_target = |name: &OsStr| {
+                        let name = name.to_string_lossy();
+                        self.plan.targets.iter().any(|target| target.matcher.is_match(&name))
+                    };
+                    self.activity.last_activity(&parent, is_target, should_continue)
+                }
+            };
+            // 项目近期有改动，不作为结果；开启 digin 时仍深入查找其中的匹配
+            if !staleness::is_idle(record.last_activity, min_idle_days) {
+                return Some(target.digin);
+            }
+        }
+
+        if self.plan.options.gitignored_only {
+            if let Some(reason) = self.ignore_checker.protection(&entry.path(), is_dir) {
+                self.progress.protected.fetch_add(1, Ordering::Relaxed);
+                let _ = self.events.blocking_send(SearchEvent::Protected(ProtectedRecord { record, reason }));
+                // 受保护的目录不是结果，其中被忽略的子目录仍可能匹配，继续深入
+                return Some(true);
+            }
+        }
+
+        // 匹配到的目录在读取它之前就要发出，所以在当前遍历线程上先统计大小，同时查找其中任一层级的保护标记，
+        // 这样发出的匹配都能整体删除
+        let should_continue = || {
+            self.session.wait_if_paused();
+            self.session.continue_search()
+        };
+        let folder_size = size::folder_size(&entry.path(), &self.plan.keep_markers, should_continue)?;
+        if let Some(keep_marker) = &folder_size.keep_marker {
+            let rule = keep_marker.file_name().unwrap_or_default().to_string_lossy().to_string();
+            let record = MatchRecord::from_entry(self.search_id, path, entry, depth, &rule);
+            self.report_skipped(record, SkipReason::KeepMarker, keep_marker.parent());
+            return Some(false);
+        }
+        let SearchOptions { min_size, min_files, .. } = &self.plan.options;
+        if min_size.is_some_and(|min_size| folder_size.bytes < min_size)
+            || min_files.is_some_and(|min_files| folder_size.files < min_files)
+        {
+            self.progress.below_threshold.fetch_add(1, Ordering::Relaxed);
+            return Some(target.digin);
+        }
+        self.counts[index].fetch_add(1, Ordering::Relaxed);
+        let _ = self.events.blocking_send(SearchEvent::Found(record));
+        let _ = self.events.blocking_send(SearchEvent::Size(folder_size));
+        Some(target.digin)
+    }
+
+    // path 是否是当前根目录以外的另一个搜索根目录；只有名称相同时才解析真实路径
+    fn is_other_root(&self, path: &Path, context: &DirContext) -> bool {
+        self.roots.iter().enumerate().any(|(index, root)| {
+            index != context.root
+                && root.real_path.file_name() == path.file_name()
+                && fs::canonicalize(path).is_ok_and(|real_path| real_path == root.real_path)
+        })
+    }
+
+    // 子目录的真实路径：普通目录直接接在上级后面，符号链接需要解析
+    // 链接指回上级目录时报告警告并返回 None，不再深入
+    fn real_dir(&self, ancestors: &Arc<RealDir>, path: &Path, is_symlink: bool, depth: usize) -> Option<Arc<RealDir>> {
+        let real_path = if is_symlink {
+            match fs::canonicalize(path) {
+                Ok(real_path) => real_path,
+                Err(err) => {
+                    self.report_error(path, depth, &err);
+                    return None;
+                }
+            }
+        } else {
+            ancestors.path.join(path.file_name()?)
+        };
+
+        if is_symlink && ancestors.contains(&real_path) {
+            self.progress.warnings.fetch_add(1, Ordering::Relaxed);
+            let warning = SearchWarning {
+                search_id: self.search_id,
+                path: path.display().to_string(),
+                depth,
+                kind: WarningKind::SymlinkLoop,
+                message: format!("Symlink loops back to {}", real_path.display()),
+            };
+            let _ = self.events.blocking_send(SearchEvent::Warning(warning));
+            return None;
+        }
+        Some(Arc::new(RealDir {
+            path: real_path,
+            parent: Some(ancestors.clone()),
+        }))
+    }
+
+    fn report_skipped(&self, record: MatchRecord, reason: SkipReason, protected: Option<&Path>) {
+        let skipped = SkippedRecord {
+            record,
+            reason,
+            protected: protected.map(|dir| dir.display().to_string()),
+        };
+        let _ = self.events.blocking_send(SearchEvent::Skipped(skipped));
+    }
+
+    fn report_error(&self, path: &Path, depth: usize, err: &io::Error) {
+        // 记录无法访问的路径，结果可能因此不完整
+        self.progress.errors.fetch_add(1, Ordering::Relaxed);
+        let error = SearchError::from_io_error(self.search_id, path, depth, err);
+        let _ = self.events.blocking_send(SearchEvent::Error(error));
+    }
+}
+
+fn case_insensitive_eq(a: &str, b: &str, casesense: bool) -> bool {
+    if casesense {
+        a == b
+    } else {
+        a.to_lowercase() == b.to_lowercase()
+    }
+}
+
+#[cfg(unix)]
+fn device_id(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device_id(_metadata: &Metadata) -> Option<u64> {
+    None
+}
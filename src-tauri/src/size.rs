@@ -0,0 +1,146 @@
+use crate::keep;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct FolderSize {
+    pub path: String,
+    pub bytes: u64,
+    pub files: u64,
+    pub dirs: u64,
+    // 删除后实际能释放的空间：同一 (设备, inode) 只计一次，被选中范围之外引用的硬链接不计入
+    pub reclaimable_bytes: u64,
+    // 统计时遇到的保护标记文件，遇到后立即停止统计；只供搜索判断匹配是否受保护，不发给前端
+    #[serde(skip)]
+    pub keep_marker: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ReclaimableSize {
+    pub bytes: u64,
+    pub reclaimable_bytes: u64,
+}
+
+// 按 (设备, inode) 去重统计可释放空间
+// pnpm / yarn berry 的 node_modules 大多是指向全局 store 的硬链接，删除只会减少链接数
+#[derive(Default)]
+struct ReclaimCounter {
+    unlinked_bytes: u64,
+    // (dev, ino) -> (nlink, 已见到的链接数, 占用空间)
+    linked: HashMap<(u64, u64), (u64, u64, u64)>,
+}
+
+impl ReclaimCounter {
+    fn add(&mut self, metadata: &Metadata) {
+        let usage = disk_usage(metadata);
+        match hard_link_key(metadata) {
+            Some((key, nlink)) if nlink > 1 && !metadata.is_dir() => {
+                let seen = self.linked.entry(key).or_insert((nlink, 0, usage));
+                seen.1 += 1;
+            }
+            _ => self.unlinked_bytes += usage,
+        }
+    }
+
+    fn reclaimable_bytes(&self) -> u64 {
+        self.unlinked_bytes
+            + self
+                .linked
+                .values()
+                .filter(|(nlink, seen, _)| seen >= nlink)
+                .map(|(_, _, usage)| usage)
+                .sum::<u64>()
+    }
+}
+
+// 统计目录或文件占用的磁盘空间、文件数和子目录数（不跟随符号链接，path 本身是链接时只统计链接）
+// 同时查找 keep_markers 中的保护标记；should_continue 返回 false 时放弃统计并返回 None
+pub fn folder_size(path: &Path, keep_markers: &[String], should_continue: impl Fn() -> bool) -> Option<FolderSize> {
+    let mut size = FolderSize {
+        path: path.display().to_string(),
+        ..Default::default()
+    };
+    let mut reclaim = ReclaimCounter::default();
+
+    for (check_counter, entry) in WalkDir::new(path).follow_root_links(false).into_iter().enumerate() {
+        if check_counter % 1000 == 0 && !should_continue() {
+            return None;
+        }
+
+        let entry = match entry {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        if entry.depth() > 0 && keep::is_keep_marker(entry.file_name(), keep_markers) {
+            size.keep_marker = Some(entry.into_path());
+            return Some(size);
+        }
+        let metadata = match entry.metadata() {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+
+        size.bytes += disk_usage(&metadata);
+        reclaim.add(&metadata);
+        // 匹配到的是文件时，文件本身计入文件数
+        if entry.depth() == 0 && !entry.file_type().is_file() {
+            continue;
+        }
+        if metadata.is_dir() {
+            size.dirs += 1;
+        } else {
+            size.files += 1;
+        }
+    }
+
+    size.reclaimable_bytes = reclaim.reclaimable_bytes();
+    Some(size)
+}
+
+// 统计一组待删除目录合起来能释放的空间，链接数在整个集合内累计
+pub fn reclaimable_size(paths: &[String]) -> ReclaimableSize {
+    let mut roots: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+    roots.sort();
+    // 去掉被其他已选目录包含的路径，避免重复统计
+    roots.dedup_by(|child, parent| child.starts_with(parent));
+
+    let mut total = ReclaimableSize::default();
+    let mut reclaim = ReclaimCounter::default();
+    for root in &roots {
+        // 删除符号链接只会移除链接本身
+        for entry in WalkDir::new(root).follow_root_links(false).into_iter().filter_map(|e| e.ok()) {
+            if let Ok(metadata) = entry.metadata() {
+                total.bytes += disk_usage(&metadata);
+                reclaim.add(&metadata);
+            }
+        }
+    }
+    total.reclaimable_bytes = reclaim.reclaimable_bytes();
+    total
+}
+
+#[cfg(unix)]
+fn disk_usage(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.blocks() * 512
+}
+
+#[cfg(not(unix))]
+fn disk_usage(metadata: &Metadata) -> u64 {
+    metadata.len()
+}
+
+#[cfg(unix)]
+fn hard_link_key(metadata: &Metadata) -> Option<((u64, u64), u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some(((metadata.dev(), metadata.ino()), metadata.nlink()))
+}
+
+// 非 unix 平台拿不到稳定的 inode 信息，按未共享处理
+#[cfg(not(unix))]
+fn hard_link_key(_metadata: &Metadata) -> Option<((u64, u64), u64)> {
+    None
+}
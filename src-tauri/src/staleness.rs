@@ -0,0 +1,91 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+// 判断项目是否活跃的依据
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StalenessSource {
+    // 项目中最新的文件修改时间
+    #[default]
+    Mtime,
+    // 所在 git 仓库 HEAD 的提交时间，不受检出、同步改写修改时间的影响；不在仓库中的项目仍按修改时间
+    Git,
+}
+
+// 项目最后活动时间：父目录下最新的文件修改时间，Unix 秒
+// 名称使 exclude 为 true 的条目（任一层级上名称匹配任一目标的文件或目录）及其内容都不计入，也不进入
+// 项目里没有其他文件时返回 None
+pub fn last_activity(
+    project: &Path,
+    exclude: impl Fn(&OsStr) -> bool,
+    should_continue: impl Fn() -> bool,
+) -> Option<u64> {
+    let mut newest = None;
+    let entries = WalkDir::new(project)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| !exclude(e.file_name()));
+
+    for (check_counter, entry) in entries.enumerate() {
+        if check_counter % 1000 == 0 && !should_continue() {
+            break;
+        }
+        let entry = match entry {
+            Ok(e) => e,
+            Err(_) => continue,
+        };
+        if entry.file_type().is_dir() {
+            continue;
+        }
+        let modified = entry
+            .metadata()
+            .ok()
+            .and_then(|m| m.modified().ok())
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs());
+        if modified > newest {
+            newest = modified;
+        }
+    }
+
+    newest
+}
+
+// 最后活动时间早于 min_idle_days 天前时视为不活跃；没有活动记录也算不活跃
+pub fn is_idle(last_activity: Option<u64>, min_idle_days: u64) -> bool {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    match last_activity {
+        Some(time) => now.saturating_sub(time) >= min_idle_days.saturating_mul(SECONDS_PER_DAY),
+        None => true,
+    }
+}
+
+// 同一次搜索中按项目缓存最后活动时间，同一父目录下的多个匹配只遍历一次
+#[derive(Default)]
+pub struct ActivityCache {
+    projects: Mutex<HashMap<PathBuf, Arc<OnceLock<Option<u64>>>>>,
+}
+
+impl ActivityCache {
+    // exclude 对同一次搜索中的所有项目都相同，所以只按项目缓存
+    pub fn last_activity(
+        &self,
+        project: &Path,
+        exclude: impl Fn(&OsStr) -> bool,
+        should_continue: impl Fn() -> bool,
+    ) -> Option<u64> {
+        let cell = self.projects.lock().unwrap().entry(project.to_path_buf()).or_default().clone();
+        // 在锁外遍历，不同项目可以并行
+        *cell.get_or_init(|| last_activity(project, exclude, should_continue))
+    }
+}
@@ -1,4 +1,4 @@
-import React, { useCallback, useEffect, useState } from "react";
+import React, { useCallback, useEffect, useRef, useState } from "react";
 import { invoke, dialog as tauriDialog } from "@tauri-apps/api";
 import { useTranslation } from "react-i18next";
 import { listen } from "@tauri-apps/api/event";
@@ -17,14 +17,52 @@ import {
   DialogContentText,
   DialogTitle,
   Chip,
+  MenuItem,
 } from "@mui/material";
-import DynamicHeightList from "./DynamicHeighhtList";
+import DynamicHeightList, {
+  FolderSize,
+  MatchRecord,
+  formatBytes,
+} from "./DynamicHeighhtList";
 import "./App.css"
 
+type MatchMode = "exact" | "substring" | "glob" | "regex";
+const matchModes: MatchMode[] = ["exact", "substring", "glob", "regex"];
+type TargetKind = "dirs" | "files" | "both";
+const targetKinds: TargetKind[] = ["dirs", "files", "both"];
+// 含路径分隔符的跳过项按路径规则处理，其余按文件夹名称
+const isSkipPath = (rule: string) => /[\\/]/.test(rule);
+type StalenessSource = "mtime" | "git";
+const stalenessSources: StalenessSource[] = ["mtime", "git"];
+
+interface Preset {
+  id: string;
+  name: string;
+  rules: { pattern: string; markers: string[] }[];
+}
+
+interface FoundBatch {
+  search_id: number;
+  batch_id: number;
+  matches: MatchRecord[];
+  sizes: FolderSize[];
+}
+
+interface SearchProgress {
+  search_id: number;
+  dirs_visited: number;
+  entries_visited: number;
+  entries_per_sec: number;
+  errors: number;
+  current_dir: string;
+}
+
 const App = () => {
   const [selectedPath, setSelectedPath] = useState("/");
+  const [extraRoots, setExtraRoots] = useState<string[]>([]);
   const [useTextInput, setUseTextInput] = useState(false);
   const [results, setResults] = useState<string[]>([]);
+  const [sizes, setSizes] = useState<Record<string, FolderSize>>({});
   const [checkedState, setCheckedState] = useState<Record<string, boolean>>({});
   const [snackbarOpen, setSnackbarOpen] = useState(false);
   const [snackbarMessage, setSnackbarMessage] = useState("");
@@ -37,27 +75,147 @@ const App = () => {
   const [skipfolders, setSkipfolders] = useState<string[]>([]);
   const [selectAll, setSelectAll] = useState(false);
   const [digin, setDigin] = useState(false);
-  const [fuzzy, setFuzzy] = useState(false);
+  const [matchMode, setMatchMode] = useState<MatchMode>("exact");
+  const [targetKind, setTargetKind] = useState<TargetKind>("dirs");
+  const [presets, setPresets] = useState<Preset[]>([]);
+  const [preset, setPreset] = useState(""); // 空字符串表示不使用预设
+  const [minIdleDays, setMinIdleDays] = useState(""); // 空字符串表示不过滤
+  const [minSizeMb, setMinSizeMb] = useState("");
+  const [minFiles, setMinFiles] = useState("");
+  const [stalenessSource, setStalenessSource] =
+    useState<StalenessSource>("mtime");
   const [casesense, setCasesense] = useState(false);
+  const [gitignoredOnly, setGitignoredOnly] = useState(false);
+  const [respectIgnoreFiles, setRespectIgnoreFiles] = useState(false);
+  const [keepMarker, setKeepMarker] = useState(".foldermanage-keep");
+  const [minDepth, setMinDepth] = useState(""); // 空字符串表示不限制
+  const [maxDepth, setMaxDepth] = useState("");
+  const [sameFilesystem, setSameFilesystem] = useState(false);
+  const [followSymlinks, setFollowSymlinks] = useState(false);
   const [isSearching, setIsSearching] = useState(false);
+  const [isPaused, setIsPaused] = useState(false);
+  const [progress, setProgress] = useState<SearchProgress | null>(null);
+  const searchIdRef = useRef<number | null>(null); // 当前搜索会话 id
+  const lastSearchIdRef = useRef(0);
+  // 搜索 id 递增，invoke 返回前到达的事件按 id 大于上一次搜索来判断
+  const isCurrentSearch = (id: number) =>
+    searchIdRef.current === null
+      ? id > lastSearchIdRef.current
+      : id === searchIdRef.current;
   const [moreSettings, setMoreSettings] = useState(false); // 新增状态来控制更多设置的显示
   const { t } = useTranslation();
 
   useEffect(() => {
-    const unlisten = listen<string>("folder-found", (event) => {
-      setResults((prevResults) => [...prevResults, event.payload]);
+    const unlisten = listen<FoundBatch>("folder-found-batch", (event) => {
+      const { search_id, batch_id, matches, sizes } = event.payload;
+      // 不再显示的搜索也要确认，否则后端会一直等待
+      invoke("ack_batch", { id: search_id, batchId: batch_id }).catch(() => {});
+      if (!isCurrentSearch(search_id)) return;
+      setResults((prevResults) => [
+        ...prevResults,
+        ...matches.map((match) => match.path),
+      ]);
+      if (sizes.length > 0) {
+        setSizes((prev) => {
+          const next = { ...prev };
+          sizes.forEach((size) => {
+            next[size.path] = size;
+          });
+          return next;
+        });
+      }
     });
-    const unlistenNoFoldersFound = listen<string>("no-folders-found", () => {
-      setSnackbarMessage(t("not_found_folders"));
-      setSnackbarOpen(true);
+    const unlistenSearchFinished = listen<{
+      search_id: number;
+      errors: number;
+      warnings: number;
+      protected: number;
+      mount_points_skipped: number;
+      below_threshold: number;
+    }>("search-finished", (event) => {
+      if (!isCurrentSearch(event.payload.search_id)) return;
+      const messages = [];
+      if (event.payload.errors > 0) {
+        messages.push(t("search_errors", { count: event.payload.errors }));
+      }
+      if (event.payload.warnings > 0) {
+        messages.push(
+          t("search_symlink_loops", { count: event.payload.warnings })
+        );
+      }
+      if (event.payload.protected > 0) {
+        messages.push(
+          t("search_protected", { count: event.payload.protected })
+        );
+      }
+      if (event.payload.mount_points_skipped > 0) {
+        messages.push(
+          t("search_mount_points_skipped", {
+            count: event.payload.mount_points_skipped,
+          })
+        );
+      }
+      if (event.payload.below_threshold > 0) {
+        messages.push(
+          t("search_below_threshold", {
+            count: event.payload.below_threshold,
+          })
+        );
+      }
+      if (messages.length > 0) {
+        setSnackbarMessage(messages.join(" "));
+        setSnackbarOpen(true);
+      }
+      lastSearchIdRef.current = event.payload.search_id;
+      searchIdRef.current = null;
+      setIsSearching(false);
+      setIsPaused(false);
+      setProgress(null);
     });
+    const unlistenSearchProgress = listen<SearchProgress>(
+      "search-progress",
+      (event) => {
+        if (!isCurrentSearch(event.payload.search_id)) return;
+        setProgress(event.payload);
+      }
+    );
+    const unlistenNoFoldersFound = listen<{ search_id: number; path: string }>(
+      "no-folders-found",
+      (event) => {
+        if (!isCurrentSearch(event.payload.search_id)) return;
+        setSnackbarMessage(t("not_found_folders"));
+        setSnackbarOpen(true);
+      }
+    );
 
     return () => {
       unlisten.then((resolve) => resolve());
+      unlistenSearchFinished.then((resolve) => resolve());
+      unlistenSearchProgress.then((resolve) => resolve());
       unlistenNoFoldersFound.then((resolve) => resolve());
     };
   }, []);
 
+  useEffect(() => {
+    invoke<Preset[]>("list_presets")
+      .then(setPresets)
+      .catch((error) => console.error("Error invoking list_presets:", error));
+    invoke<string>("get_keep_marker")
+      .then(setKeepMarker)
+      .catch((error) => console.error("Error invoking get_keep_marker:", error));
+  }, []);
+
+  // 保护标记由后端保存，搜索和删除都使用它；保存失败时恢复为后端当前的设置
+  const saveKeepMarker = async () => {
+    try {
+      await invoke("set_keep_marker", { marker: keepMarker });
+    } catch (error) {
+      setSnackbarMessage(`${error}`);
+      setSnackbarOpen(true);
+      setKeepMarker(await invoke<string>("get_keep_marker"));
+    }
+  };
+
   useEffect(() => {
     if (selectAll) {
       const newCheckedState = results.reduce<{ [key: string]: boolean }>(
@@ -75,13 +233,26 @@ const App = () => {
 
   const stopSearch = async () => {
     try {
-      await invoke("stop_search");
-      setIsSearching(false);
+      if (searchIdRef.current !== null) {
+        await invoke("stop_search", { id: searchIdRef.current });
+      }
     } catch (error) {
       console.error("Error stopping the search:", error);
     }
   };
 
+  const togglePauseSearch = async () => {
+    if (searchIdRef.current === null) return;
+    try {
+      await invoke(isPaused ? "resume_search" : "pause_search", {
+        id: searchIdRef.current,
+      });
+      setIsPaused(!isPaused);
+    } catch (error) {
+      console.error("Error pausing/resuming the search:", error);
+    }
+  };
+
   const handleSelectAllChange = (event: {
     target: { checked: boolean | ((prevState: boolean) => boolean) };
   }) => {
@@ -106,29 +277,83 @@ const App = () => {
     }
   };
 
+  const addSearchRoots = async () => {
+    try {
+      const paths = await tauriDialog.open({ directory: true, multiple: true });
+      if (paths) {
+        const added = Array.isArray(paths) ? paths : [paths];
+        setExtraRoots((prev) => [
+          ...prev,
+          ...added.filter((path) => !prev.includes(path)),
+        ]);
+      }
+    } catch (error) {
+      console.error("Error selecting directory:", error);
+    }
+  };
+
+  const handleRemoveSearchRoot = (index: number) => {
+    setExtraRoots((prev) => prev.filter((_, i) => i !== index));
+  };
+
   const searchNodeModules = async () => {
-    if (!selectedPath || !foldername) {
+    if ((!selectedPath && extraRoots.length === 0) || (!foldername && !preset)) {
       setSnackbarMessage(t("no_path_or_folder_selected"));
       setSnackbarOpen(true);
       return;
     }
 
+    // 界面只显示一个搜索，开始新搜索前停止仍在进行的搜索并忽略它之后的事件
+    if (searchIdRef.current !== null) {
+      invoke("stop_search", { id: searchIdRef.current }).catch(() => {});
+      lastSearchIdRef.current = searchIdRef.current;
+      searchIdRef.current = null;
+    }
+
     setResults([]);
+    setSizes({});
     setCheckedState({});
     setIsSearching(true);
 
     try {
-      await invoke("search_folders", {
-        path: selectedPath,
-        foldername,
-        skipfolders,
-        digin,
-        fuzzy,
-        casesense,
+      const id = await invoke<number>("search_folders", {
+        options: {
+          path: selectedPath,
+          roots: extraRoots,
+          targets: foldername
+            .split(",")
+            .map((name) => name.trim())
+            .filter((name) => name),
+          skipfolders: skipfolders.filter((rule) => !isSkipPath(rule)),
+          skipPaths: skipfolders.filter(isSkipPath),
+          digin,
+          matchMode,
+          targetKind,
+          preset: preset || null,
+          minIdleDays: minIdleDays ? Number(minIdleDays) : null,
+          minSize: minSizeMb
+            ? Math.round(Number(minSizeMb) * 1024 * 1024)
+            : null,
+          minFiles: minFiles ? Number(minFiles) : null,
+          stalenessSource,
+          casesense,
+          gitignoredOnly,
+          respectIgnoreFiles,
+          minDepth: minDepth ? Number(minDepth) : null,
+          maxDepth: maxDepth ? Number(maxDepth) : null,
+          sameFilesystem,
+          followSymlinks,
+          ackBatches: true,
+        },
       });
-      setIsSearching(false);
+      if (id > lastSearchIdRef.current) {
+        lastSearchIdRef.current = id - 1;
+        searchIdRef.current = id;
+      }
     } catch (error) {
       console.error("Error invoking search_folders:", error);
+      setSnackbarMessage(String(error));
+      setSnackbarOpen(true);
       setIsSearching(false);
     }
   };
@@ -150,6 +375,20 @@ const App = () => {
     setToDeleteFiles(selectedFiles);
     setOpenDialog(true);
     setDialogContent(t("delete_confirmation"));
+    try {
+      const size = await invoke<{ bytes: number; reclaimable_bytes: number }>(
+        "reclaimable_size",
+        { paths: selectedFiles }
+      );
+      setDialogContent(
+        t("delete_confirmation_reclaimable", {
+          size: formatBytes(size.reclaimable_bytes),
+          total: formatBytes(size.bytes),
+        })
+      );
+    } catch (error) {
+      console.error("Error invoking reclaimable_size:", error);
+    }
   };
 
   const handleDialogClose = async (confirm: boolean) => {
@@ -162,15 +401,35 @@ const App = () => {
   const performDeletion = async (files: string[]) => {
     try {
       setIsDeleting(true); // 设置删除状态为true
-      await invoke("delete_folders", { paths: files });
-      setSnackbarMessage(t("files_deleted_success"));
+      const outcomes = await invoke<
+        { path: string; action: string; protected: string | null }[]
+      >(
+        "delete_folders",
+        { paths: files }
+      );
+      // 符号链接只移除了链接本身，提示用户链接目标仍然保留
+      const unlinked = outcomes.filter(
+        (outcome) => outcome.action === "unlinked_symlink"
+      ).length;
+      // 含有保护标记的路径被跳过，其余路径照常删除
+      const protectedCount = outcomes.filter(
+        (outcome) => outcome.action === "protected"
+      ).length;
+      const notes = [
+        unlinked > 0 ? t("symlinks_unlinked", { count: unlinked }) : null,
+        protectedCount > 0
+          ? t("delete_protected_skipped", { count: protectedCount })
+          : null,
+      ].filter(Boolean);
+      setSnackbarMessage([t("files_deleted_success"), ...notes].join(" "));
       setSnackbarOpen(true);
       searchNodeModules();
       setIsDeleting(false); // 完成删除后设置状态为false
     } catch (error) {
       console.error("Error invoking delete_folders:", error);
-      setSnackbarMessage(t("failed_delete_files"));
+      setSnackbarMessage(`${t("failed_delete_files")}: ${error}`);
       setSnackbarOpen(true);
+      setIsDeleting(false);
     }
   };
 
@@ -212,18 +471,36 @@ const App = () => {
       toggle: setDigin,
       minWidth: 205,
     },
-    {
-      label: t("fuzzy_search_switch"),
-      value: fuzzy,
-      toggle: setFuzzy,
-      minWidth: 160,
-    },
     {
       label: t("case_sensitive_switch"),
       value: casesense,
       toggle: setCasesense,
       minWidth: 170,
     },
+    {
+      label: t("gitignored_only_switch"),
+      value: gitignoredOnly,
+      toggle: setGitignoredOnly,
+      minWidth: 230,
+    },
+    {
+      label: t("respect_ignore_files_switch"),
+      value: respectIgnoreFiles,
+      toggle: setRespectIgnoreFiles,
+      minWidth: 230,
+    },
+    {
+      label: t("same_filesystem_switch"),
+      value: sameFilesystem,
+      toggle: setSameFilesystem,
+      minWidth: 230,
+    },
+    {
+      label: t("follow_symlinks_switch"),
+      value: followSymlinks,
+      toggle: setFollowSymlinks,
+      minWidth: 200,
+    },
   ];
 
   return (
@@ -321,6 +598,119 @@ const App = () => {
                   {selectedPath || t("select_folder_path_button")}
                 </Button>
               )}
+              <Button variant="outlined" onClick={addSearchRoots}>
+                {t("add_search_path_button")}
+              </Button>
+            </Box>
+            {extraRoots.length > 0 && (
+              <Box
+                sx={{
+                  display: "flex",
+                  gap: 1,
+                  flexWrap: "wrap",
+                  padding: 1,
+                  backgroundColor: "#e3f2fd",
+                  borderRadius: "8px",
+                  marginTop: 1,
+                }}
+              >
+                {extraRoots.map((root, index) => (
+                  <Chip
+                    key={root}
+                    label={root}
+                    onDelete={() => handleRemoveSearchRoot(index)}
+                    color="primary"
+                    variant="outlined"
+                    sx={{ margin: "4px" }}
+                  />
+                ))}
+              </Box>
+            )}
+            <Box
+              sx={{
+                display: "flex",
+                alignItems: "center",
+                gap: 2,
+                marginTop: 2,
+                minHeight: 40,
+              }}
+            >
+              <TextField
+                label={t("min_idle_days_label")}
+                type="number"
+                value={minIdleDays}
+                onChange={(e) => setMinIdleDays(e.target.value)}
+                variant="outlined"
+                size="small"
+                inputProps={{ min: 0 }}
+                sx={{ minWidth: "240px" }}
+              />
+              <TextField
+                label={t("min_size_label")}
+                type="number"
+                value={minSizeMb}
+                onChange={(e) => setMinSizeMb(e.target.value)}
+                variant="outlined"
+                size="small"
+                inputProps={{ min: 0 }}
+                sx={{ width: "140px" }}
+              />
+              <TextField
+                label={t("min_files_label")}
+                type="number"
+                value={minFiles}
+                onChange={(e) => setMinFiles(e.target.value)}
+                variant="outlined"
+                size="small"
+                inputProps={{ min: 0 }}
+                sx={{ width: "120px" }}
+              />
+              <TextField
+                label={t("min_depth_label")}
+                type="number"
+                value={minDepth}
+                onChange={(e) => setMinDepth(e.target.value)}
+                variant="outlined"
+                size="small"
+                inputProps={{ min: 1 }}
+                sx={{ width: "120px" }}
+              />
+              <TextField
+                label={t("max_depth_label")}
+                type="number"
+                value={maxDepth}
+                onChange={(e) => setMaxDepth(e.target.value)}
+                variant="outlined"
+                size="small"
+                inputProps={{ min: 1 }}
+                sx={{ width: "120px" }}
+              />
+              <TextField
+                label={t("keep_marker_label")}
+                value={keepMarker}
+                onChange={(e) => setKeepMarker(e.target.value)}
+                onBlur={saveKeepMarker}
+                variant="outlined"
+                size="small"
+                sx={{ minWidth: "200px" }}
+              />
+              <TextField
+                select
+                label={t("staleness_source_label")}
+                value={stalenessSource}
+                onChange={(e) =>
+                  setStalenessSource(e.target.value as StalenessSource)
+                }
+                variant="outlined"
+                size="small"
+                sx={{ minWidth: "200px" }}
+              >
+                {stalenessSources.map((source) => (
+                  <MenuItem key={source} value={source}>
+                    {t(`staleness_source_${source}`)}
+                  </MenuItem>
+                ))}
+              </TextField>
             </Box>
           </Box>
           <Button
@@ -351,16 +741,80 @@ const App = () => {
               variant="outlined"
               sx={{ flex: 1, margin: "1rem" }}
             />
+            <TextField
+              select
+              label={t("match_mode_label")}
+              value={matchMode}
+              onChange={(e) => setMatchMode(e.target.value as MatchMode)}
+              variant="outlined"
+              sx={{ minWidth: 140, mr: 2 }}
+            >
+              {matchModes.map((mode) => (
+                <MenuItem key={mode} value={mode}>
+                  {t(`match_mode_${mode}`)}
+                </MenuItem>
+              ))}
+            </TextField>
+            <TextField
+              select
+              label={t("target_kind_label")}
+              value={targetKind}
+              onChange={(e) => setTargetKind(e.target.value as TargetKind)}
+              variant="outlined"
+              sx={{ minWidth: 140, mr: 2 }}
+            >
+              {targetKinds.map((kind) => (
+                <MenuItem key={kind} value={kind}>
+                  {t(`target_kind_${kind}`)}
+                </MenuItem>
+              ))}
+            </TextField>
+            <TextField
+              select
+              label={t("preset_label")}
+              value={preset}
+              onChange={(e) => setPreset(e.target.value)}
+              variant="outlined"
+              sx={{ minWidth: 160, mr: 2 }}
+            >
+              <MenuItem value="">{t("preset_none")}</MenuItem>
+              {presets.map((p) => (
+                <MenuItem
+                  key={p.id}
+                  value={p.id}
+                  title={p.rules
+                    .map((rule) =>
+                      rule.markers.length
+                        ? `${rule.pattern} (${rule.markers.join(" / ")})`
+                        : rule.pattern
+                    )
+                    .join(", ")}
+                >
+                  {p.name}
+                </MenuItem>
+              ))}
+            </TextField>
             <Box>
               {isSearching ? (
-                <Button
-                  variant="contained"
-                  color="secondary"
-                  onClick={stopSearch}
-                  disabled={!isSearching}
-                >
-                  {t("stop_search_button")}
-                </Button>
+                <>
+                  <Button
+                    variant="outlined"
+                    onClick={togglePauseSearch}
+                    sx={{ mr: 1 }}
+                  >
+                    {isPaused
+                      ? t("resume_search_button")
+                      : t("pause_search_button")}
+                  </Button>
+                  <Button
+                    variant="contained"
+                    color="secondary"
+                    onClick={stopSearch}
+                    disabled={!isSearching}
+                  >
+                    {t("stop_search_button")}
+                  </Button>
+                </>
               ) : (
                 <Button
                   variant="contained"
@@ -423,9 +877,25 @@ const App = () => {
           <Typography>
             {t("folders_found", { count: results.length })}
           </Typography>
+          {isSearching && progress && (
+            <Typography
+              noWrap
+              variant="body2"
+              sx={{ color: "text.secondary" }}
+              title={progress.current_dir}
+            >
+              {t("search_progress", {
+                dirs: progress.dirs_visited,
+                rate: Math.round(progress.entries_per_sec),
+                errors: progress.errors,
+                current: progress.current_dir,
+              })}
+            </Typography>
+          )}
           <Box sx={{ flex: 1, width: "100%", overflow: "auto" }}>
             <DynamicHeightList
               items={results}
+              sizes={sizes}
               checkedState={checkedState}
               handleCheckChange={handleCheckChange}
               openDirectory={openDirectory}
@@ -4,8 +4,49 @@ import { Box, Checkbox, Typography, IconButton } from '@mui/material';
 import FolderOpenIcon from '@mui/icons-material/FolderOpen';
 import {useResizeObserver} from "./useResizeObserver"
 
+export interface MatchRecord {
+  search_id: number;
+  path: string;
+  root: string;
+  depth: number;
+  mtime: number | null;
+  is_symlink: boolean;
+  is_file: boolean;
+  rule: string;
+  marker: string | null;
+  last_activity: number | null;
+  git: GitStatus | null;
+}
+
+export interface GitStatus {
+  repository: string;
+  head: string | null;
+  commit_time: number | null;
+  dirty: boolean;
+}
+
+export interface FolderSize {
+  path: string;
+  bytes: number;
+  files: number;
+  dirs: number;
+  reclaimable_bytes: number;
+}
+
+export const formatBytes = (bytes: number) => {
+  const units = ['B', 'KB', 'MB', 'GB', 'TB'];
+  let value = bytes;
+  let unit = 0;
+  while (value >= 1024 && unit < units.length - 1) {
+    value /= 1024;
+    unit++;
+  }
+  return `${value.toFixed(unit === 0 ? 0 : 1)} ${units[unit]}`;
+};
+
 interface DynamicHeightListProps {
   items: string[];
+  sizes: Record<string, FolderSize>;
   checkedState: Record<string, boolean>;
   handleCheckChange: (file: string) => (event: React.ChangeEvent<HTMLInputElement>) => void;
   openDirectory: (path: string) => void;
@@ -13,6 +54,7 @@ interface DynamicHeightListProps {
 
 const DynamicHeightList: React.FC<DynamicHeightListProps> = ({
   items,
+  sizes,
   checkedState,
   handleCheckChange,
   openDirectory,
@@ -56,6 +98,15 @@ const DynamicHeightList: React.FC<DynamicHeightListProps> = ({
               >
                 {items[index]}
               </Typography>
+              {sizes[items[index]] && (
+                <Typography
+                  noWrap
+                  sx={{ minWidth: 90, textAlign: 'right', color: 'text.secondary' }}
+                  title={`${sizes[items[index]].files} files, ${sizes[items[index]].dirs} dirs, ${formatBytes(sizes[items[index]].reclaimable_bytes)} reclaimable`}
+                >
+                  {formatBytes(sizes[items[index]].bytes)}
+                </Typography>
+              )}
               <IconButton
                 onClick={() => openDirectory(items[index])}
                 color='primary'